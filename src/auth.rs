@@ -0,0 +1,422 @@
+use crate::CloudVisionError;
+use async_trait::async_trait;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+use tokio::sync::Mutex;
+
+/// On-prem CVP endpoint that exchanges a username and password for a session token
+pub const LOGIN_URL: &str = "/cvpservice/login/authenticate.do";
+
+/// Session tokens are renewed this long before they expire so in-flight requests don't race the
+/// expiry
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// Supplies the token used to authenticate requests. The client asks for a token before every
+/// request, so providers that read from somewhere slow should cache it.
+#[async_trait]
+pub trait TokenProvider: Send + Sync + fmt::Debug {
+    /// The token to send with the next request
+    async fn token(&self) -> Result<String, CloudVisionError>;
+
+    /// Called when CloudVision rejected `token` with a 401. Providers that can fetch a different
+    /// token should drop it and return true, the client then repeats the request once.
+    async fn invalidate(&self, _rejected: &str) -> bool {
+        false
+    }
+}
+
+/// A token that never changes
+pub struct StaticToken(String);
+
+impl StaticToken {
+    pub fn new(token: String) -> Self {
+        StaticToken(token)
+    }
+}
+
+impl fmt::Debug for StaticToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("StaticToken(..)")
+    }
+}
+
+#[async_trait]
+impl TokenProvider for StaticToken {
+    async fn token(&self) -> Result<String, CloudVisionError> {
+        Ok(self.0.clone())
+    }
+}
+
+/// A token read from a file, such as one rendered by Vault agent. The file is read again
+/// whenever its modification time changes, so rotated tokens are picked up without rebuilding
+/// the client.
+pub struct TokenFile {
+    path: PathBuf,
+    cached: Mutex<Option<(SystemTime, String)>>,
+}
+
+impl TokenFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            cached: Mutex::new(None),
+        }
+    }
+
+    fn error(&self, err: std::io::Error) -> CloudVisionError {
+        CloudVisionError::TokenSource(format!(
+            "could not read token file {}: {}",
+            self.path.display(),
+            err
+        ))
+    }
+}
+
+impl fmt::Debug for TokenFile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TokenFile")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl TokenProvider for TokenFile {
+    async fn token(&self) -> Result<String, CloudVisionError> {
+        let modified = tokio::fs::metadata(&self.path)
+            .await
+            .and_then(|metadata| metadata.modified())
+            .map_err(|err| self.error(err))?;
+        let mut cached = self.cached.lock().await;
+        if let Some((read_at, token)) = cached.as_ref() {
+            if *read_at == modified {
+                return Ok(token.clone());
+            }
+        }
+        let token = tokio::fs::read_to_string(&self.path)
+            .await
+            .map_err(|err| self.error(err))?
+            .trim()
+            .to_owned();
+        if token.is_empty() {
+            return Err(CloudVisionError::NoToken);
+        }
+        *cached = Some((modified, token.clone()));
+        Ok(token)
+    }
+}
+
+/// A token read from an environment variable before every request
+#[derive(Debug)]
+pub struct EnvToken {
+    var: String,
+}
+
+impl EnvToken {
+    pub fn new(var: String) -> Self {
+        Self { var }
+    }
+}
+
+#[async_trait]
+impl TokenProvider for EnvToken {
+    async fn token(&self) -> Result<String, CloudVisionError> {
+        std::env::var(&self.var).map_err(|_| CloudVisionError::MissingEnvVar(self.var.clone()))
+    }
+}
+
+/// A token printed to stdout by an external command, such as a secrets manager CLI. The output
+/// is cached until CloudVision rejects it, or until the optional time to live passes.
+pub struct CommandToken {
+    program: String,
+    args: Vec<String>,
+    ttl: Option<Duration>,
+    cached: Mutex<Option<(Instant, String)>>,
+}
+
+impl CommandToken {
+    pub fn new(program: String, args: Vec<String>) -> Self {
+        Self {
+            program,
+            args,
+            ttl: None,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// Runs the command again once its last output is older than `ttl`
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = Some(ttl);
+        self
+    }
+
+    async fn run(&self) -> Result<String, CloudVisionError> {
+        let output = Command::new(&self.program)
+            .args(&self.args)
+            .output()
+            .await
+            .map_err(|err| {
+                CloudVisionError::TokenSource(format!("could not run {}: {}", self.program, err))
+            })?;
+        if !output.status.success() {
+            return Err(CloudVisionError::TokenSource(format!(
+                "{} exited with {}",
+                self.program, output.status
+            )));
+        }
+        let token = String::from_utf8_lossy(&output.stdout).trim().to_owned();
+        if token.is_empty() {
+            return Err(CloudVisionError::NoToken);
+        }
+        Ok(token)
+    }
+}
+
+impl fmt::Debug for CommandToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CommandToken")
+            .field("program", &self.program)
+            .field("args", &self.args)
+            .field("ttl", &self.ttl)
+            .finish_non_exhaustive()
+    }
+}
+
+#[async_trait]
+impl TokenProvider for CommandToken {
+    async fn token(&self) -> Result<String, CloudVisionError> {
+        let mut cached = self.cached.lock().await;
+        if let Some((ran_at, token)) = cached.as_ref() {
+            if self.ttl.is_none_or(|ttl| ran_at.elapsed() < ttl) {
+                return Ok(token.clone());
+            }
+        }
+        let token = self.run().await?;
+        *cached = Some((Instant::now(), token.clone()));
+        Ok(token)
+    }
+
+    async fn invalidate(&self, rejected: &str) -> bool {
+        let mut cached = self.cached.lock().await;
+        if cached.as_ref().is_some_and(|(_, token)| token == rejected) {
+            *cached = None;
+        }
+        true
+    }
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LoginRequest<'a> {
+    user_id: &'a str,
+    password: &'a str,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TokenResponse {
+    #[serde(rename = "sessionId")]
+    session_id: Option<String>,
+    cookie: Option<CookieResponse>,
+    /// Set instead of a token when the credentials were rejected, CVP still answers with a 200
+    #[serde(rename = "errorCode")]
+    error_code: Option<String>,
+    #[serde(rename = "errorMessage")]
+    error_message: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct CookieResponse {
+    #[serde(rename = "Value")]
+    value: String,
+}
+
+impl TokenResponse {
+    /// The `access_token` cookie value, falling back to the session id on older releases
+    fn into_token(self) -> Result<String, CloudVisionError> {
+        if self.error_code.is_some() || self.error_message.is_some() {
+            return Err(CloudVisionError::LoginRejected {
+                code: self.error_code.unwrap_or_default(),
+                message: self.error_message.unwrap_or_default(),
+            });
+        }
+        self.cookie
+            .map(|cookie| cookie.value)
+            .or(self.session_id)
+            .ok_or(CloudVisionError::NoToken)
+    }
+}
+
+#[derive(Debug)]
+struct SessionToken {
+    value: String,
+    expires: Option<SystemTime>,
+}
+
+impl SessionToken {
+    fn is_fresh(&self) -> bool {
+        match self.expires {
+            Some(expires) => SystemTime::now() + EXPIRY_MARGIN < expires,
+            None => true,
+        }
+    }
+}
+
+/// A username and password login shared by a `Client` and its clones. The session token is
+/// fetched on first use, reused until it is about to expire, and fetched again after a 401.
+pub(crate) struct Session {
+    username: String,
+    password: String,
+    token: Mutex<Option<SessionToken>>,
+}
+
+impl fmt::Debug for Session {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Session")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Session {
+    pub(crate) fn new(username: String, password: String) -> Self {
+        Self {
+            username,
+            password,
+            token: Mutex::new(None),
+        }
+    }
+
+    /// Returns the cached session token, logging in first if there is none or it is about to
+    /// expire. `login` posts the JSON body it is given to `LOGIN_URL` and returns the response
+    /// body. Concurrent callers wait for a single login.
+    pub(crate) async fn token<F, Fut>(&self, login: F) -> Result<String, CloudVisionError>
+    where
+        F: FnOnce(String) -> Fut,
+        Fut: Future<Output = Result<String, CloudVisionError>>,
+    {
+        let mut token = self.token.lock().await;
+        if let Some(current) = token.as_ref().filter(|current| current.is_fresh()) {
+            return Ok(current.value.clone());
+        }
+        let request = serde_json::to_string(&LoginRequest {
+            user_id: &self.username,
+            password: &self.password,
+        })?;
+        let response: TokenResponse = serde_json::from_str(&login(request).await?)?;
+        let value = response.into_token()?;
+        let fresh = SessionToken {
+            expires: jwt_expiry(&value),
+            value: value.clone(),
+        };
+        *token = Some(fresh);
+        Ok(value)
+    }
+
+    /// Drops the cached token after it was rejected, unless another caller already replaced it
+    pub(crate) async fn invalidate(&self, rejected: &str) {
+        let mut token = self.token.lock().await;
+        if token
+            .as_ref()
+            .is_some_and(|current| current.value == rejected)
+        {
+            *token = None;
+        }
+    }
+}
+
+/// Reads the `exp` claim of a JWT without verifying it, `None` if the token is not a JWT
+fn jwt_expiry(token: &str) -> Option<SystemTime> {
+    #[derive(Deserialize)]
+    struct Claims {
+        exp: u64,
+    }
+    let payload = token.split('.').nth(1)?;
+    let claims: Claims = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()?;
+    Some(UNIX_EPOCH + Duration::from_secs(claims.exp))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jwt_expiry() {
+        let payload = URL_SAFE_NO_PAD.encode(r#"{"exp":1673476197,"sid":"abc"}"#);
+        let token = format!("eyJhbGciOiJFUzI1NiJ9.{}.c2lnbmF0dXJl", payload);
+        assert_eq!(
+            jwt_expiry(&token),
+            Some(UNIX_EPOCH + Duration::from_secs(1673476197))
+        );
+        assert_eq!(jwt_expiry("opaque-session-id"), None);
+
+        let expired = SessionToken {
+            expires: jwt_expiry(&token),
+            value: token,
+        };
+        assert!(!expired.is_fresh());
+    }
+
+    #[tokio::test]
+    async fn test_token_file() {
+        let path = std::env::temp_dir().join("cloudvision-token-file.txt");
+        tokio::fs::write(&path, "first\n").await.unwrap();
+        let provider = TokenFile::new(path.clone());
+        assert_eq!(provider.token().await.unwrap(), "first");
+        // A rotated file is picked up once its modification time changes
+        tokio::fs::write(&path, "second\n").await.unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(provider.token().await.unwrap(), "second");
+        tokio::fs::remove_file(&path).await.unwrap();
+        assert!(matches!(
+            provider.token().await,
+            Err(CloudVisionError::TokenSource(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_command_token() {
+        let provider = CommandToken::new("echo".to_string(), vec!["token".to_string()]);
+        assert_eq!(provider.token().await.unwrap(), "token");
+        assert!(provider.invalidate("token").await);
+        assert_eq!(provider.token().await.unwrap(), "token");
+        let provider = CommandToken::new("false".to_string(), Vec::new());
+        assert!(matches!(
+            provider.token().await,
+            Err(CloudVisionError::TokenSource(_))
+        ));
+    }
+
+    #[test]
+    fn test_token_response() {
+        let response: TokenResponse = serde_json::from_str(
+            r#"{"sessionId":"session","userName":"cvpadmin","cookie":{"Name":"access_token","Value":"cookie"}}"#,
+        )
+        .unwrap();
+        assert_eq!(response.into_token().unwrap(), "cookie");
+        let response: TokenResponse = serde_json::from_str(r#"{"sessionId":"session"}"#).unwrap();
+        assert_eq!(response.into_token().unwrap(), "session");
+        let response: TokenResponse =
+            serde_json::from_str(r#"{"errorCode":"112498","errorMessage":"Unauthorized User"}"#)
+                .unwrap();
+        match response.into_token() {
+            Err(CloudVisionError::LoginRejected { code, message }) => {
+                assert_eq!(code, "112498");
+                assert_eq!(message, "Unauthorized User");
+            }
+            other => panic!("expected a rejected login, got {:?}", other),
+        }
+        let response: TokenResponse = serde_json::from_str("{}").unwrap();
+        assert!(matches!(
+            response.into_token(),
+            Err(CloudVisionError::NoToken)
+        ));
+    }
+}
@@ -0,0 +1,280 @@
+use crate::filter::FilterBuilder;
+use crate::resource::Resource;
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub const CHANGE_CONTROL_URL: &str = "/api/resources/changecontrol/v1/ChangeControl";
+pub const CHANGE_CONTROL_ALL_URL: &str = "/api/resources/changecontrol/v1/ChangeControl/all";
+pub const CHANGE_CONTROL_SUBSCRIBE_URL: &str =
+    "/api/resources/changecontrol/v1/ChangeControl/subscribe";
+pub const CHANGE_CONTROL_UPDATE_URL: &str = "/api/v3/services/ccapi.ChangeControl/Update";
+pub const CHANGE_CONTROL_APPROVE_URL: &str = "/api/v3/services/ccapi.ChangeControl/AddApproval";
+pub const CHANGE_CONTROL_START_URL: &str = "/api/v3/services/ccapi.ChangeControl/Start";
+
+/// A change control as reported by the resource API
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct ChangeControl {
+    pub key: ChangeControlKey,
+    pub change: Option<Change>,
+    pub status: Option<ChangeControlStatus>,
+    pub error: Option<String>,
+}
+
+impl Resource for ChangeControl {
+    type Match = ChangeControlMatch;
+
+    const GET_URL: &'static str = CHANGE_CONTROL_URL;
+    const ALL_URL: &'static str = CHANGE_CONTROL_ALL_URL;
+    const SUBSCRIBE_URL: &'static str = CHANGE_CONTROL_SUBSCRIBE_URL;
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ChangeControlKey {
+    pub id: String,
+}
+
+/// A filter entry for change controls, see `FilterBuilder<ChangeControlMatch>`
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct ChangeControlMatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<ChangeControlKey>,
+}
+
+impl FilterBuilder<ChangeControlMatch> {
+    pub fn id(mut self, id: &str) -> Self {
+        self.entry().key = Some(ChangeControlKey { id: id.to_owned() });
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ChangeControlStatus {
+    ChangeControlStatusRunning,
+    ChangeControlStatusCompleted,
+    ChangeControlStatusScheduled,
+    /// Also a status this version of the crate does not know about
+    #[serde(other)]
+    ChangeControlStatusUnspecified,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct Change {
+    pub name: String,
+    pub root_stage_id: String,
+    pub stages: StageMap,
+    pub notes: Option<String>,
+    pub time: Option<Timestamp>,
+    pub user: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StageMap {
+    pub values: HashMap<String, Stage>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Stage {
+    pub name: String,
+    pub action: Option<Action>,
+    pub rows: Option<StageRows>,
+    pub status: Option<StageStatus>,
+    pub error: Option<String>,
+}
+
+/// Ids of the stages run by a stage, one entry per row run in sequence
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StageRows {
+    pub values: Vec<StageIds>,
+}
+
+/// Ids of the stages in a row, which run in parallel
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StageIds {
+    pub values: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Action {
+    pub name: String,
+    pub timeout: Option<u32>,
+    pub args: Option<Arg>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Arg {
+    pub values: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum StageStatus {
+    StageStatusUnspecified,
+    StageStatusRunning,
+    StageStatusCompleted,
+}
+
+/// The body of a ccapi `Update` call, which creates or replaces a change control
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChangeUpdate {
+    pub config: ChangeConfig,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChangeConfig {
+    pub id: String,
+    pub name: String,
+    pub root_stage: RootStage,
+}
+
+impl ChangeConfig {
+    pub fn new(name: String, root_stage: RootStage) -> Self {
+        let id = Uuid::new_v4().to_string();
+        ChangeConfig {
+            id,
+            name,
+            root_stage,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RootStage {
+    pub id: String,
+    pub name: String,
+    pub stage_row: Vec<StageRow>,
+}
+
+impl RootStage {
+    pub fn new(name: String, stage_row: Vec<StageRow>) -> Self {
+        let id = Uuid::new_v4().to_string();
+        RootStage {
+            id,
+            name,
+            stage_row,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StageRow {
+    pub stage: Vec<StageConfig>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StageConfig {
+    pub id: String,
+    pub name: String,
+    pub action: ActionConfig,
+}
+
+impl StageConfig {
+    pub fn new(name: String, action: ActionConfig) -> Self {
+        let id = Uuid::new_v4().to_string();
+        StageConfig { id, name, action }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ActionConfig {
+    pub name: String,
+    pub args: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Approval {
+    pub cc_id: String,
+    pub cc_timestamp: Timestamp,
+}
+
+impl Approval {
+    /// Approves the change control `cc_id` as it was at `cc_timestamp`, the time of its last
+    /// update
+    pub fn new(cc_id: &str, cc_timestamp: Timestamp) -> Self {
+        Approval {
+            cc_id: cc_id.to_owned(),
+            cc_timestamp,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct StartChange {
+    pub cc_id: String,
+}
+
+impl StartChange {
+    pub fn new(cc_id: &str) -> Self {
+        StartChange {
+            cc_id: cc_id.to_owned(),
+        }
+    }
+}
+
+/// The response to the ccapi calls, which only report when the change was recorded
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChangeControlUpdated {
+    pub time: Option<Timestamp>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::StreamResponse;
+    use crate::stream::ServiceResponse;
+
+    #[test]
+    fn test_change_config_ids() {
+        let action = ActionConfig {
+            name: "task".to_string(),
+            args: HashMap::from([("TaskID".to_string(), "1234".to_string())]),
+        };
+        let stage = StageConfig::new("run task".to_string(), action);
+        let root = RootStage::new("root".to_string(), vec![StageRow { stage: vec![stage] }]);
+        let config = ChangeConfig::new("upgrade".to_string(), root);
+        assert_ne!(config.id, config.root_stage.id);
+        assert!(Uuid::parse_str(&config.id).is_ok());
+        let json = serde_json::to_value(ChangeUpdate { config }).unwrap();
+        assert_eq!(
+            json["config"]["root_stage"]["stage_row"][0]["stage"][0]["action"]["args"]["TaskID"],
+            "1234"
+        );
+    }
+
+    #[test]
+    fn test_approval() {
+        let time = "2022-02-10T21:43:03.5Z".parse().unwrap();
+        assert_eq!(
+            serde_json::to_string(&Approval::new("cc1", time)).unwrap(),
+            r#"{"cc_id":"cc1","cc_timestamp":"2022-02-10T21:43:03.500Z"}"#
+        );
+    }
+
+    #[test]
+    fn test_decode_change_control() {
+        let record = r#"{"result":{"value":{"key":{"id":"cc1"},"change":{"name":"upgrade","rootStageId":"s1","stages":{"values":{"s1":{"name":"root","rows":{"values":[{"values":["s2"]}]}},"s2":{"name":"task","action":{"name":"task","timeout":3000,"args":{"values":{"TaskID":"1234"}}}}}}},"status":"CHANGE_CONTROL_STATUS_COMPLETED"},"time":"2022-02-10T21:43:03.5Z","type":"INITIAL"}}"#;
+        let record = serde_json::from_str::<StreamResponse<ChangeControl>>(record)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        assert_eq!(record.value().key.id, "cc1");
+        let change = record.value().change.as_ref().unwrap();
+        assert_eq!(change.root_stage_id, "s1");
+        let rows = change.stages.values["s1"].rows.as_ref().unwrap();
+        assert_eq!(rows.values[0].values, ["s2"]);
+        assert!(matches!(
+            record.value().status,
+            Some(ChangeControlStatus::ChangeControlStatusCompleted)
+        ));
+
+        let status = serde_json::from_str(r#""CHANGE_CONTROL_STATUS_PAUSED""#).unwrap();
+        assert!(matches!(
+            status,
+            ChangeControlStatus::ChangeControlStatusUnspecified
+        ));
+    }
+}
@@ -1,41 +1,36 @@
+use crate::filter::FilterBuilder;
+use crate::resource::Resource;
+use crate::timestamp::Timestamp;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "lowercase")]
-pub enum DeviceServiceResponse {
-    Result(Box<DeviceStreamResponse>),
-    Error,
-}
+pub const DEVICE_GET_URL: &str = "/api/resources/inventory/v1/Device";
+pub const DEVICE_URL: &str = "/api/resources/inventory/v1/Device/all";
+pub const DEVICE_SUBSCRIBE_URL: &str = "/api/resources/inventory/v1/Device/subscribe";
 
-// TODO use proper time instead of string
 #[derive(Serialize, Deserialize, Debug)]
 pub struct DeviceRequest {
     key: DeviceKey,
-    time: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<Timestamp>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct DeviceResponse {
-    value: Device,
-    time: String,
-}
+impl DeviceRequest {
+    pub fn new(device_id: &str) -> Self {
+        DeviceRequest {
+            key: DeviceKey::new(device_id),
+            time: None,
+        }
+    }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct DeviceStreamRequest {
-    partial_eq_filter: Vec<Device>,
-    time: String,
+    /// Reads the device as it was at the given time instead of its current state
+    pub fn at(mut self, time: Timestamp) -> Self {
+        self.time = Some(time);
+        self
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct DeviceStreamResponse {
-    value: Device,
-    time: String,
-    #[serde(rename = "type")]
-    operation_type: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
 pub struct Device {
     key: DeviceKey,
     software_version: String,
@@ -45,18 +40,127 @@ pub struct Device {
     hostname: String,
     domain_name: String,
     system_mac_address: String,
-    boot_time: String,
+    boot_time: Option<Timestamp>,
     streaming_status: StreamingStatus,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Resource for Device {
+    type Match = DeviceMatch;
+
+    const GET_URL: &'static str = DEVICE_GET_URL;
+    const ALL_URL: &'static str = DEVICE_URL;
+    const SUBSCRIBE_URL: &'static str = DEVICE_SUBSCRIBE_URL;
+}
+
+impl Device {
+    pub fn key(&self) -> &DeviceKey {
+        &self.key
+    }
+
+    /// The serial number of the device
+    pub fn device_id(&self) -> &str {
+        self.key.device_id()
+    }
+
+    pub fn software_version(&self) -> &str {
+        &self.software_version
+    }
+
+    pub fn model_name(&self) -> &str {
+        &self.model_name
+    }
+
+    pub fn hardware_revision(&self) -> &str {
+        &self.hardware_revision
+    }
+
+    pub fn fqdn(&self) -> &str {
+        &self.fqdn
+    }
+
+    pub fn hostname(&self) -> &str {
+        &self.hostname
+    }
+
+    pub fn domain_name(&self) -> &str {
+        &self.domain_name
+    }
+
+    pub fn system_mac_address(&self) -> &str {
+        &self.system_mac_address
+    }
+
+    /// Time the device last booted, use `boot_time().elapsed()` for its uptime
+    pub fn boot_time(&self) -> Option<Timestamp> {
+        self.boot_time
+    }
+
+    pub fn streaming_status(&self) -> StreamingStatus {
+        self.streaming_status
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct DeviceKey {
     device_id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl DeviceKey {
+    pub fn new(device_id: &str) -> Self {
+        DeviceKey {
+            device_id: device_id.to_owned(),
+        }
+    }
+
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+}
+
+/// A filter entry for devices, every field that is set must match. Built with the setters on
+/// `FilterBuilder<DeviceMatch>`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceMatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<DeviceKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    software_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hostname: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    streaming_status: Option<StreamingStatus>,
+}
+
+impl FilterBuilder<DeviceMatch> {
+    pub fn device_id(mut self, device_id: &str) -> Self {
+        self.entry().key = Some(DeviceKey::new(device_id));
+        self
+    }
+    pub fn model_name(mut self, model_name: &str) -> Self {
+        self.entry().model_name = Some(model_name.to_owned());
+        self
+    }
+    pub fn software_version(mut self, software_version: &str) -> Self {
+        self.entry().software_version = Some(software_version.to_owned());
+        self
+    }
+    pub fn streaming_status(mut self, streaming_status: StreamingStatus) -> Self {
+        self.entry().streaming_status = Some(streaming_status);
+        self
+    }
+    pub fn hostname(mut self, hostname: &str) -> Self {
+        self.entry().hostname = Some(hostname.to_owned());
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum StreamingStatus {
+    #[default]
     #[serde(rename = "STREAMING_STATUS_UNSPECIFIED")]
     Unspecified,
     #[serde(rename = "STREAMING_STATUS_INACTIVE")]
@@ -64,3 +168,61 @@ pub enum StreamingStatus {
     #[serde(rename = "STREAMING_STATUS_ACTIVE")]
     Active,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::resource::StreamResponse;
+    use crate::stream::ServiceResponse;
+    use crate::{Client, Config};
+    use std::path::Path;
+
+    #[test]
+    fn test_device_accessors() {
+        let record = r#"{"result":{"value":{"key":{"deviceId":"SSJ17200818"},"softwareVersion":"4.27.2F","modelName":"DCS-7280SR2-48YC6","hardwareRevision":"11.00","fqdn":"leaf1.example.com","hostname":"leaf1","domainName":"example.com","systemMacAddress":"00:1c:73:aa:bb:cc","bootTime":"2022-02-01T10:00:00Z","streamingStatus":"STREAMING_STATUS_ACTIVE"},"time":"2022-02-10T21:43:03Z","type":"INITIAL"}}"#;
+        let record = serde_json::from_str::<StreamResponse<Device>>(record)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let device = record.value();
+        assert_eq!(device.device_id(), "SSJ17200818");
+        assert_eq!(device.key(), &DeviceKey::new("SSJ17200818"));
+        assert_eq!(device.hostname(), "leaf1");
+        assert_eq!(device.fqdn(), "leaf1.example.com");
+        assert_eq!(device.software_version(), "4.27.2F");
+        assert_eq!(device.system_mac_address(), "00:1c:73:aa:bb:cc");
+        assert_eq!(device.streaming_status(), StreamingStatus::Active);
+        assert_eq!(device.boot_time(), "2022-02-01T10:00:00Z".parse().ok());
+
+        let request = DeviceRequest::new("SSJ17200818").at(record.time());
+        assert_eq!(
+            serde_json::to_string(&request).unwrap(),
+            r#"{"key":{"deviceId":"SSJ17200818"},"time":"2022-02-10T21:43:03Z"}"#
+        );
+    }
+
+    #[test]
+    fn test_masked_device() {
+        // A field mask only returns the requested fields
+        let record = r#"{"result":{"value":{"key":{"deviceId":"SSJ17200818"},"hostname":"leaf1"},"time":"2022-02-10T21:43:03Z","type":"INITIAL"}}"#;
+        let record = serde_json::from_str::<StreamResponse<Device>>(record)
+            .unwrap()
+            .into_result()
+            .unwrap();
+        let device = record.value();
+        assert_eq!(device.device_id(), "SSJ17200818");
+        assert_eq!(device.hostname(), "leaf1");
+        assert_eq!(device.model_name(), "");
+        assert_eq!(device.boot_time(), None);
+        assert_eq!(device.streaming_status(), StreamingStatus::Unspecified);
+    }
+
+    #[tokio::test]
+    async fn test_get_device() {
+        let client =
+            Client::new(Config::from_file(Path::new("config/cloudvision.config")).unwrap())
+                .unwrap();
+        let device = client.get_device("SSJ17200818").send().await.unwrap();
+        assert_eq!(device.value().model_name(), "DCS-7280SR2-48YC6");
+    }
+}
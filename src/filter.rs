@@ -0,0 +1,193 @@
+use crate::resource::TimeBounds;
+use crate::timestamp::Timestamp;
+use serde::{Deserialize, Serialize};
+
+/// Body of a GetAll or Subscribe request, the entries to match and optionally a time in the past
+/// to read the state at. A resource matches when every field set in any one entry matches, so
+/// several entries are ORed together. Build one with `Filter::builder`, for example
+/// `Filter::<DeviceMatch>::builder().model_name("DCS-7280SR2-48YC6").build()`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Filter<T> {
+    partial_eq_filter: Vec<T>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<TimeBounds>,
+}
+
+impl<T> Default for Filter<T> {
+    fn default() -> Self {
+        Self {
+            partial_eq_filter: Vec::new(),
+            time: None,
+        }
+    }
+}
+
+impl<T> Filter<T> {
+    /// A filter that matches everything
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Starts building a filter, the setters available depend on the resource
+    pub fn builder() -> FilterBuilder<T> {
+        FilterBuilder {
+            entries: Vec::new(),
+            current: None,
+            time: None,
+        }
+    }
+
+    /// Reads the state as it was at the given time instead of the current state
+    pub fn at(mut self, time: Timestamp) -> Self {
+        self.time = Some(TimeBounds::at(time));
+        self
+    }
+
+    /// Reads every change between start and end instead of the current state
+    pub fn between(mut self, start: Timestamp, end: Timestamp) -> Self {
+        self.time = Some(TimeBounds::between(start, end));
+        self
+    }
+
+    /// The entries a resource is matched against, empty when the filter matches everything
+    pub fn entries(&self) -> &[T] {
+        &self.partial_eq_filter
+    }
+
+    /// The time to read the state at, if any
+    pub fn time(&self) -> Option<TimeBounds> {
+        self.time
+    }
+
+    /// Removes the time from the filter and returns it
+    pub(crate) fn take_time(&mut self) -> Option<TimeBounds> {
+        self.time.take()
+    }
+}
+
+/// Builds a `Filter` one entry at a time. Setters add to the current entry, `or` starts a new one.
+/// The setters for each resource live next to its model, such as `FilterBuilder<DeviceMatch>`
+/// in the `device` module.
+#[derive(Debug, Clone)]
+pub struct FilterBuilder<T> {
+    entries: Vec<T>,
+    current: Option<T>,
+    time: Option<TimeBounds>,
+}
+
+impl<T> FilterBuilder<T> {
+    /// Finishes the current entry, resources matching it or any entry after it are also returned
+    pub fn or(mut self) -> Self {
+        self.entries.extend(self.current.take());
+        self
+    }
+
+    /// Reads the state as it was at the given time instead of the current state
+    pub fn at(mut self, time: Timestamp) -> Self {
+        self.time = Some(TimeBounds::at(time));
+        self
+    }
+
+    /// Reads every change between start and end instead of the current state
+    pub fn between(mut self, start: Timestamp, end: Timestamp) -> Self {
+        self.time = Some(TimeBounds::between(start, end));
+        self
+    }
+
+    pub fn build(mut self) -> Filter<T> {
+        self.entries.extend(self.current.take());
+        Filter {
+            partial_eq_filter: self.entries,
+            time: self.time,
+        }
+    }
+}
+
+impl<T: Default> FilterBuilder<T> {
+    /// The entry the setters add to
+    pub(crate) fn entry(&mut self) -> &mut T {
+        self.current.get_or_insert_with(T::default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{DeviceMatch, StreamingStatus};
+    use crate::tag::{ElementType, TagAssignmentMatch, TagMatch};
+
+    #[test]
+    fn test_filter_time() {
+        let filter = Filter::<TagMatch>::all();
+        assert_eq!(
+            serde_json::to_string(&filter).unwrap(),
+            r#"{"partial_eq_filter":[]}"#
+        );
+        let start: Timestamp = "2022-02-10T21:00:00Z".parse().unwrap();
+        let end: Timestamp = "2022-02-10T22:00:00.5Z".parse().unwrap();
+        let filter = Filter::<TagMatch>::all().at(end);
+        assert_eq!(
+            serde_json::to_string(&filter).unwrap(),
+            r#"{"partial_eq_filter":[],"time":{"end":"2022-02-10T22:00:00.500Z"}}"#
+        );
+        let filter = Filter::<TagMatch>::builder().between(start, end).build();
+        assert_eq!(
+            serde_json::to_string(&filter).unwrap(),
+            r#"{"partial_eq_filter":[],"time":{"start":"2022-02-10T21:00:00Z","end":"2022-02-10T22:00:00.500Z"}}"#
+        );
+    }
+
+    #[test]
+    fn test_device_filter() {
+        let filter = Filter::<DeviceMatch>::builder()
+            .model_name("DCS-7280SR2-48YC6")
+            .streaming_status(StreamingStatus::Active)
+            .or()
+            .hostname("leaf1")
+            .or()
+            .or()
+            .build();
+        assert_eq!(filter.entries().len(), 2);
+        assert_eq!(
+            serde_json::to_string(&filter).unwrap(),
+            r#"{"partial_eq_filter":[{"modelName":"DCS-7280SR2-48YC6","streamingStatus":"STREAMING_STATUS_ACTIVE"},{"hostname":"leaf1"}]}"#
+        );
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let filter = Filter::<TagMatch>::builder()
+            .label("site")
+            .value("dc1")
+            .element_type(ElementType::Device)
+            .or()
+            .workspace("ws1")
+            .build();
+        let json = serde_json::to_value(&filter).unwrap();
+        let entries = json["partial_eq_filter"].as_array().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0]["key"]["label"], "site");
+        assert_eq!(entries[0]["key"]["value"], "dc1");
+        assert_eq!(entries[0]["key"]["elementType"], "ELEMENT_TYPE_DEVICE");
+        assert_eq!(entries[1]["key"]["workspaceId"], "ws1");
+
+        // Unset fields are left out rather than sent as null
+        let filter = Filter::<TagMatch>::builder().label("site").build();
+        assert_eq!(
+            serde_json::to_string(&filter).unwrap(),
+            r#"{"partial_eq_filter":[{"key":{"label":"site"}}]}"#
+        );
+    }
+
+    #[test]
+    fn test_tag_assignment_filter() {
+        let filter = Filter::<TagAssignmentMatch>::builder()
+            .device_id("SSJ17200818")
+            .interface_id("Ethernet1")
+            .build();
+        assert_eq!(
+            serde_json::to_string(&filter).unwrap(),
+            r#"{"partial_eq_filter":[{"key":{"deviceId":"SSJ17200818","interfaceId":"Ethernet1"}}]}"#
+        );
+    }
+}
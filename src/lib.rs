@@ -1,15 +1,49 @@
 use crate::tag::*;
+use futures::{future, StreamExt};
 use reqwest::header::*;
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::path::Path;
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 use std::{env, fs};
-use url::{ParseError, Url};
+use tokio::sync::OwnedSemaphorePermit;
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+use url::Url;
 
+pub mod auth;
+pub mod changecontrol;
 pub mod client;
 pub mod device;
-pub mod tag;
+pub mod filter;
 pub mod inventory;
+pub mod middleware;
+mod ratelimit;
+pub mod request;
+pub mod resource;
+pub mod retry;
+pub mod stream;
+pub mod tag;
+#[cfg(test)]
+mod testing;
+pub mod timestamp;
+mod tls;
+
+use crate::auth::{Session, StaticToken, TokenFile, TokenProvider};
+use crate::filter::Filter;
+use crate::middleware::{Exchange, Middleware};
+use crate::ratelimit::{RateLimitConfig, RateLimiter};
+use crate::request::{Call, Get, GetAll};
+use crate::resource::{ResourceResponse, StreamResponse, WatchEvent};
+use crate::retry::{RequestKind, RetryPolicy};
+use crate::stream::{PartialResult, RecordError, RecordStream, ServiceResponse};
+use crate::tls::TlsConfig;
 
 /// Wraps error types when working with CloudVision APIs or parsing
 #[derive(Debug)]
@@ -19,9 +53,172 @@ pub enum CloudVisionError {
     JsonParse(serde_json::Error),
     UrlParse(url::ParseError),
     BadClientPort,
+    /// 401, the token is missing, invalid or expired
+    Unauthorized(ApiError),
+    /// 403, the token is valid but lacks permission for the request
+    Forbidden(ApiError),
+    /// 404, the requested resource does not exist
+    NotFound(ApiError),
+    /// 429, the client is being throttled
+    RateLimited(ApiError),
+    /// Any 5xx response
+    Server(ApiError),
+    /// Any other non-success response
+    UnexpectedStatus(ApiError),
+    /// A record in a streamed response could not be decoded or was an in-band error
+    Record(RecordError),
+    /// A required environment variable is not set
+    MissingEnvVar(String),
+    /// An environment variable is set to a value that can not be used
+    InvalidEnvVar {
+        name: String,
+        value: String,
+    },
+    /// The config file could not be read
+    ConfigRead {
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// The config file is not valid TOML or does not match the expected layout, the TOML error
+    /// carries the line and column
+    ConfigParse {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+    /// No config file was found at any of the searched paths
+    NoConfigFile(Vec<PathBuf>),
+    /// The config file has no profile with the requested name
+    UnknownProfile {
+        name: String,
+        path: PathBuf,
+    },
+    /// A token provider could not produce a token
+    TokenSource(String),
+    /// CVP refused the username and password login
+    LoginRejected {
+        code: String,
+        message: String,
+    },
+    /// A CA bundle, client certificate or pinned fingerprint could not be used
+    Tls(String),
+    /// The proxy settings can not be used
+    Proxy(String),
+    /// The request did not finish within the configured timeout
+    Timeout {
+        url: Option<Url>,
+    },
+    /// A header name or value supplied to a middleware is not valid
+    InvalidHeader(String),
+    /// A request was built with options that can not be used together
+    InvalidRequest(String),
 }
+
+impl CloudVisionError {
+    /// Maps a non-success HTTP response to the matching error variant
+    pub(crate) fn from_status(status: StatusCode, path: &str, body: &str) -> Self {
+        let err = ApiError::new(status, path, body);
+        match status {
+            StatusCode::UNAUTHORIZED => CloudVisionError::Unauthorized(err),
+            StatusCode::FORBIDDEN => CloudVisionError::Forbidden(err),
+            StatusCode::NOT_FOUND => CloudVisionError::NotFound(err),
+            StatusCode::TOO_MANY_REQUESTS => CloudVisionError::RateLimited(err),
+            s if s.is_server_error() => CloudVisionError::Server(err),
+            _ => CloudVisionError::UnexpectedStatus(err),
+        }
+    }
+
+    /// True for errors that may go away if the request is repeated, such as a dropped
+    /// connection or a 5xx response
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            CloudVisionError::Request(_)
+                | CloudVisionError::Timeout { .. }
+                | CloudVisionError::RateLimited(_)
+                | CloudVisionError::Server(_)
+        )
+    }
+
+    /// Returns the API error details when the error came from a non-success HTTP response
+    pub fn api_error(&self) -> Option<&ApiError> {
+        match self {
+            CloudVisionError::Unauthorized(err)
+            | CloudVisionError::Forbidden(err)
+            | CloudVisionError::NotFound(err)
+            | CloudVisionError::RateLimited(err)
+            | CloudVisionError::Server(err)
+            | CloudVisionError::UnexpectedStatus(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CloudVisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CloudVisionError::NoToken => write!(f, "no token available"),
+            CloudVisionError::Request(err) => write!(f, "request failed: {}", err),
+            CloudVisionError::JsonParse(err) => write!(f, "could not parse JSON: {}", err),
+            CloudVisionError::UrlParse(err) => write!(f, "could not parse url: {}", err),
+            CloudVisionError::BadClientPort => write!(f, "port can not be set on this url"),
+            CloudVisionError::Unauthorized(err)
+            | CloudVisionError::Forbidden(err)
+            | CloudVisionError::NotFound(err)
+            | CloudVisionError::RateLimited(err)
+            | CloudVisionError::Server(err)
+            | CloudVisionError::UnexpectedStatus(err) => write!(f, "{}", err),
+            CloudVisionError::Record(err) => write!(f, "{}", err),
+            CloudVisionError::MissingEnvVar(name) => {
+                write!(f, "environment variable {} is not set", name)
+            }
+            CloudVisionError::InvalidEnvVar { name, value } => {
+                write!(
+                    f,
+                    "environment variable {} has invalid value {:?}",
+                    name, value
+                )
+            }
+            CloudVisionError::ConfigRead { path, source } => {
+                write!(f, "could not read {}: {}", path.display(), source)
+            }
+            CloudVisionError::ConfigParse { path, source } => {
+                write!(f, "could not parse {}: {}", path.display(), source)
+            }
+            CloudVisionError::NoConfigFile(paths) => {
+                let paths: Vec<_> = paths
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect();
+                write!(f, "no config file found, searched {}", paths.join(", "))
+            }
+            CloudVisionError::UnknownProfile { name, path } => {
+                write!(f, "no profile named {} in {}", name, path.display())
+            }
+            CloudVisionError::TokenSource(message) => write!(f, "{}", message),
+            CloudVisionError::LoginRejected { code, message } => {
+                write!(f, "login rejected: {} ({})", message, code)
+            }
+            CloudVisionError::Tls(message) => write!(f, "TLS configuration error: {}", message),
+            CloudVisionError::Proxy(message) => write!(f, "proxy configuration error: {}", message),
+            CloudVisionError::Timeout { url: Some(url) } => {
+                write!(f, "request to {} timed out", url)
+            }
+            CloudVisionError::Timeout { url: None } => write!(f, "request timed out"),
+            CloudVisionError::InvalidHeader(header) => write!(f, "invalid header: {:?}", header),
+            CloudVisionError::InvalidRequest(message) => write!(f, "invalid request: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CloudVisionError {}
+
 impl From<reqwest::Error> for CloudVisionError {
     fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            return CloudVisionError::Timeout {
+                url: err.url().cloned(),
+            };
+        }
         CloudVisionError::Request(err)
     }
 }
@@ -36,31 +233,249 @@ impl From<url::ParseError> for CloudVisionError {
     }
 }
 
-#[derive(Debug)]
+/// A non-success HTTP response along with the error body decoded from the gRPC gateway
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiError {
+    status: StatusCode,
+    path: String,
+    body: ErrorBody,
+}
+
+impl ApiError {
+    /// Builds the error from the raw response body. Bodies that are not a gateway error, such as
+    /// an HTML page from a load balancer, are kept as the message.
+    pub(crate) fn new(status: StatusCode, path: &str, body: &str) -> Self {
+        let body = match serde_json::from_str::<GatewayError>(body) {
+            Ok(GatewayError::Wrapped { error }) | Ok(GatewayError::Bare(error)) => error,
+            Err(_) => ErrorBody {
+                message: body.to_owned(),
+                ..ErrorBody::default()
+            },
+        };
+        Self {
+            status,
+            path: path.to_owned(),
+            body,
+        }
+    }
+    /// HTTP status returned by CloudVision
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+    /// Path of the request that failed
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Decoded error body
+    pub fn body(&self) -> &ErrorBody {
+        &self.body
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} from {}", self.status, self.path)?;
+        if !self.body.message.is_empty() {
+            write!(f, ": {}", self.body.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// The error body sent by the gRPC gateway, either on its own or wrapped in an `error` object as
+/// it is inside a stream
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum GatewayError {
+    Wrapped { error: ErrorBody },
+    Bare(ErrorBody),
+}
+
+/// Error body returned by CloudVision, `code` is the gRPC status code
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct ErrorBody {
+    code: i32,
+    message: String,
+    details: Vec<serde_json::Value>,
+}
+
+impl ErrorBody {
+    /// gRPC status code
+    pub fn code(&self) -> i32 {
+        self.code
+    }
+    /// Human readable error message
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+    /// Additional error details as sent by the server
+    pub fn details(&self) -> &[serde_json::Value] {
+        &self.details
+    }
+}
+
+/// Passes through a success response, turning non-success statuses into errors
+pub(crate) async fn check_status(
+    response: reqwest::Response,
+) -> Result<reqwest::Response, CloudVisionError> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    let path = response.url().path().to_owned();
+    let body = response.text().await?;
+    Err(CloudVisionError::from_status(status, &path, &body))
+}
+
+/// A response whose headers have arrived. It counts as in flight for the rate limiter until its
+/// body has been read or it is dropped.
+struct InFlight {
+    response: reqwest::Response,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl InFlight {
+    async fn check_status(self) -> Result<Self, CloudVisionError> {
+        Ok(Self {
+            response: check_status(self.response).await?,
+            permit: self.permit,
+        })
+    }
+
+    /// The body chunks, holding the rate limiter permit until the stream is dropped
+    fn bytes_stream(self) -> impl futures::Stream<Item = reqwest::Result<impl AsRef<[u8]>>> {
+        let permit = self.permit;
+        self.response.bytes_stream().map(move |chunk| {
+            let _held = &permit;
+            chunk
+        })
+    }
+}
+
+/// A CloudVision client. The underlying HTTP connection pool is built once when the client is
+/// created and is shared by every call made through it, including calls made through clones.
+#[derive(Debug, Clone)]
 pub struct Client {
     base_url: Url,
-    token: String,
+    auth: Auth,
+    transport: Transport,
+    retry: RetryPolicy,
+    limiter: Arc<RateLimiter>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    http: reqwest::Client,
+}
+
+/// The settings the HTTP client is built from, kept so it can be rebuilt with changes
+#[derive(Debug, Clone)]
+struct Transport {
     accept_invalid_certs: bool,
+    pool: PoolConfig,
+    tls: TlsConfig,
+    proxy: ProxyConfig,
+    timeouts: TimeoutConfig,
+}
+
+/// How requests are authenticated
+#[derive(Debug, Clone)]
+enum Auth {
+    /// A token supplied by a provider before each request
+    Provider(Arc<dyn TokenProvider>),
+    /// A session token obtained by logging in to on-prem CVP, shared by all clones
+    Session(Arc<Session>),
 }
 
 impl Client {
-    /// Create a new client based on a config
+    /// Create a new client based on a config. A token in the config is used as is, then a token
+    /// file, otherwise the client logs in with the configured username and password on first use.
     pub fn new(config: Config) -> Result<Self, CloudVisionError> {
         let url = format!("https://{}/", &config.hostname);
         let mut url = Url::parse(&url)?;
         url.set_port(config.port)
             .map_err(|_| CloudVisionError::BadClientPort)?;
+        let transport = Transport {
+            accept_invalid_certs: config.accept_invalid_certs,
+            pool: config.pool,
+            tls: config.tls,
+            proxy: config.proxy,
+            timeouts: config.timeouts,
+        };
+        let http = build_http_client(&transport)?;
+        let auth = match (
+            config.token,
+            config.token_file,
+            config.username,
+            config.password,
+        ) {
+            (token, _, _, _) if !token.is_empty() => {
+                Auth::Provider(Arc::new(StaticToken::new(token)))
+            }
+            (_, Some(path), _, _) => Auth::Provider(Arc::new(TokenFile::new(path))),
+            (_, _, Some(username), Some(password)) => {
+                Auth::Session(Arc::new(Session::new(username, password)))
+            }
+            _ => return Err(CloudVisionError::NoToken),
+        };
         Ok(Self {
             base_url: url,
-            token: config.token,
-            accept_invalid_certs: config.accept_invalid_certs,
+            auth,
+            transport,
+            retry: config.retry,
+            limiter: Arc::new(RateLimiter::new(&config.rate_limit)),
+            middleware: Vec::new(),
+            http,
         })
     }
 
     /// Use to allow or disallow invalid certificates when making calls, default is false, use this
-    /// to set to true
-    pub fn set_accept_invalid_certs(&mut self, accept: bool) {
-        self.accept_invalid_certs = accept;
+    /// to set to true. This rebuilds the connection pool, so existing clones keep their settings.
+    pub fn set_accept_invalid_certs(&mut self, accept: bool) -> Result<(), CloudVisionError> {
+        let mut transport = self.transport.clone();
+        transport.accept_invalid_certs = accept;
+        self.http = build_http_client(&transport)?;
+        self.transport = transport;
+        Ok(())
+    }
+
+    /// Replaces how requests are authenticated, the provider is asked for a token before every
+    /// request. This only affects this client and clones made from it afterwards.
+    pub fn set_token_provider(&mut self, provider: Arc<dyn TokenProvider>) {
+        self.auth = Auth::Provider(provider);
+    }
+
+    /// Replaces how failed requests are retried, see `RetryPolicy`
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    /// Adds a middleware to the end of the chain every request passes through, see `Middleware`.
+    /// This only affects this client and clones made from it afterwards.
+    pub fn add_middleware(&mut self, middleware: Arc<dyn Middleware>) {
+        self.middleware.push(middleware);
+    }
+
+    /// Returns a client for calls that must finish within `timeout`, including retries and
+    /// reading the response. Streams only have to start within it. The connection pool is
+    /// shared with this client.
+    pub fn with_timeout(&self, timeout: Duration) -> Self {
+        let mut client = self.clone();
+        client.transport.timeouts.request_secs = Some(timeout.as_secs_f64());
+        client
+    }
+
+    /// Returns a client for calls that fail when the server sends nothing for `timeout`, while
+    /// waiting for a response or between chunks of its body
+    pub fn with_read_timeout(&self, timeout: Duration) -> Self {
+        let mut client = self.clone();
+        client.transport.timeouts.read_secs = Some(timeout.as_secs_f64());
+        client
+    }
+
+    /// A clone without a read timeout, for subscriptions that may legitimately stay quiet. Dead
+    /// connections are still noticed through TCP keep-alive, see `Config::set_tcp_keepalive`.
+    pub(crate) fn without_read_timeout(mut self) -> Self {
+        self.transport.timeouts.read_secs = None;
+        self
     }
 
     /// Returns a clone of the base url
@@ -75,130 +490,796 @@ impl Client {
         url
     }
 
-    /// Given an API path, perform a GET and return the result or Error
-    /// TODO: return something better than a String maybe return the raw response
-    /// then you can run the .json() decoder from reqwest?
+    /// Like `build_url`, with the query parameters appended
+    pub(crate) fn query_url(&self, path: &str, query: &[(&str, &str)]) -> Url {
+        let mut url = self.build_url(path);
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        url
+    }
+
+    /// Given an API path, perform a GET and return the raw response body or Error, see `get_json`
+    /// to decode it
     pub async fn get(&self, path: &str) -> Result<String, CloudVisionError> {
-        let url = self.build_url(path);
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(self.accept_invalid_certs)
-            .build()?;
-        let response = client
-            .get(url)
-            .header(ACCEPT, "application/json")
-            .bearer_auth(&self.token)
-            .send()
-            .await?
-            .text()
-            .await?;
-        Ok(response)
+        self.get_url(self.build_url(path)).await
     }
 
-    /// Given an API path, perform a GET and return the result or Error
-    /// TODO: return something better than a String
+    /// Given an API path, perform a POST and return the raw response body or Error, see
+    /// `post_json` to encode the body and decode the response.
+    /// The POST is treated as a write, so it is only retried if the retry policy allows writes.
     pub async fn post(&self, path: &str, body: String) -> Result<String, CloudVisionError> {
-        let url = self.build_url(path);
-        let client = reqwest::Client::builder()
-            .danger_accept_invalid_certs(self.accept_invalid_certs)
-            .build()?;
-        let response = client
-            .post(url)
-            .header(ACCEPT, "application/json")
-            .bearer_auth(&self.token)
-            .body(body)
-            .send()
-            .await?
-            .text()
+        self.post_url(self.build_url(path), body).await
+    }
+
+    /// Performs a GET with the given query parameters and decodes the JSON response, such as a
+    /// resource API `Get`
+    pub async fn get_json<R: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<R, CloudVisionError> {
+        self.send_json(
+            Method::GET,
+            self.query_url(path, query),
+            None,
+            RequestKind::Read,
+        )
+        .await
+    }
+
+    /// Performs a POST with `body` encoded as JSON and decodes the JSON response. The POST is
+    /// treated as a write, so it is only retried if the retry policy allows writes.
+    pub async fn post_json<T, R>(&self, path: &str, body: &T) -> Result<R, CloudVisionError>
+    where
+        T: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        let body = serde_json::to_string(body)?;
+        self.send_json(
+            Method::POST,
+            self.build_url(path),
+            Some(body),
+            RequestKind::Write,
+        )
+        .await
+    }
+
+    /// Performs a PUT with `body` encoded as JSON and decodes the JSON response, such as a
+    /// resource API `Set`. Only retried if the retry policy allows writes.
+    pub async fn put<T, R>(&self, path: &str, body: &T) -> Result<R, CloudVisionError>
+    where
+        T: Serialize + ?Sized,
+        R: DeserializeOwned,
+    {
+        let body = serde_json::to_string(body)?;
+        self.send_json(
+            Method::PUT,
+            self.build_url(path),
+            Some(body),
+            RequestKind::Write,
+        )
+        .await
+    }
+
+    /// Performs a DELETE with the given query parameters and decodes the JSON response, such as
+    /// a resource API `Delete`. Only retried if the retry policy allows writes.
+    pub async fn delete<R: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<R, CloudVisionError> {
+        self.send_json(
+            Method::DELETE,
+            self.query_url(path, query),
+            None,
+            RequestKind::Write,
+        )
+        .await
+    }
+
+    /// Performs a GET against one of the resource API `/all` endpoints with the given query
+    /// parameters, such as `time`, and decodes every record. Fails on the first record that can
+    /// not be decoded.
+    pub async fn get_all<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<Vec<ResourceResponse<T>>, CloudVisionError> {
+        self.fetch_all::<StreamResponse<T>>(Method::GET, self.query_url(path, query), None)
+            .await
+    }
+
+    /// Sends a request and decodes the JSON response
+    pub(crate) async fn send_json<R: DeserializeOwned>(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<String>,
+        kind: RequestKind,
+    ) -> Result<R, CloudVisionError> {
+        let response = self.fetch(method, url, body, kind).await?;
+        Ok(serde_json::from_str(&response)?)
+    }
+
+    /// Sends a request to one of the resource API `/all` endpoints, which only read state, and
+    /// decodes every record. Fails on the first record that can not be decoded.
+    pub(crate) async fn fetch_all<R: ServiceResponse>(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<String>,
+    ) -> Result<Vec<R::Record>, CloudVisionError> {
+        let (response, span) = self
+            .fetch_traced(method, url, body, RequestKind::Read)
             .await?;
-        Ok(response)
+        let records = stream::decode_strict::<R>(&response)?;
+        span.record("records", records.len());
+        Ok(records)
     }
 
-    pub async fn get_change_control(&self, key: &str) -> Result<String, CloudVisionError> {
-        let path = "/api/resources/tag/v2/ChangeControl/all";
-        self.get(path).await
+    /// Performs a GET against a fully built url using the shared connection pool
+    pub(crate) async fn get_url(&self, url: Url) -> Result<String, CloudVisionError> {
+        self.fetch(Method::GET, url, None, RequestKind::Read).await
     }
 
-    /// Gets inventory matching the specified key and filter to get all use an empty filter
-    pub async fn get_devices(
+    /// Performs a POST against a fully built url using the shared connection pool
+    pub(crate) async fn post_url(
         &self,
-        filter: &PartialEqFilter,
-    ) -> Result<Vec<device::DeviceServiceResponse>, CloudVisionError> {
-        let path = "/api/resources/inventory/v1/Device/all";
-        let json_data = serde_json::to_string(filter)?;
-        let response = self.post(path, json_data).await?;
-        // Using a stream Deserializer to parse the returned stream of JSON
-        let dsr: Vec<device::DeviceServiceResponse> = serde_json::Deserializer::from_str(&response)
-            .into_iter::<device::DeviceServiceResponse>()
-            .filter_map(|x| x.ok())
-            .collect();
-        Ok(dsr)
-    }
-
-    /// get_tag_assignment_config will retreive configurations for tag assignment between network
-    /// element and tag
-    pub async fn get_tag_assignment_config(
+        url: Url,
+        body: String,
+    ) -> Result<String, CloudVisionError> {
+        self.fetch(Method::POST, url, Some(body), RequestKind::Write)
+            .await
+    }
+
+    /// Sends a request and reads the whole response body, all within the request timeout
+    async fn fetch(
         &self,
-        filter: &PartialEqFilter,
-    ) -> Result<Vec<tag::TagAssignmentServiceResponse>, CloudVisionError> {
-        let path = tag::TAG_ASSIGNMENT_CONFIG_URL;
-        let json_data = serde_json::to_string(filter)?;
-        let response = self.post(path, json_data).await?;
-        // Using a stream Deserializer to parse the returned stream of JSON
-        let tacr: Vec<tag::TagAssignmentServiceResponse> =
-            serde_json::Deserializer::from_str(&response)
-                .into_iter::<tag::TagAssignmentServiceResponse>()
-                .filter_map(|x| x.ok())
-                .collect();
-        Ok(tacr)
-    }
-
-    /// Gets tags matching the specified key and filter
-    pub async fn get_tags(
+        method: Method,
+        url: Url,
+        body: Option<String>,
+        kind: RequestKind,
+    ) -> Result<String, CloudVisionError> {
+        let (body, _span) = self.fetch_traced(method, url, body, kind).await?;
+        Ok(body)
+    }
+
+    /// Like `fetch`, but also returns the span the request was traced in so the caller can add
+    /// the number of records it decoded
+    async fn fetch_traced(
         &self,
-        filter: &PartialEqFilter,
-    ) -> Result<Vec<tag::TagServiceResponse>, CloudVisionError> {
-        let path = "/api/resources/tag/v2/Tag/all";
-        let json_data = serde_json::to_string(filter)?;
-        let response = self.post(path, json_data).await?;
-        let tsr: Vec<tag::TagServiceResponse> = serde_json::Deserializer::from_str(&response)
-            .into_iter::<tag::TagServiceResponse>()
-            .filter_map(|x| x.ok())
-            .collect();
-        Ok(tsr)
-    }
-
-    /// Gets all tags
-    pub async fn get_all_tags(&self) -> Result<Vec<tag::TagServiceResponse>, CloudVisionError> {
-        let workspace_key = TagKey::new();
-        let filter = Tag::new(workspace_key);
-        let data = PartialEqFilter {
-            partial_eq_filter: vec![filter],
-        };
-        self.get_tags(&data).await
+        method: Method,
+        url: Url,
+        body: Option<String>,
+        kind: RequestKind,
+    ) -> Result<(String, Span), CloudVisionError> {
+        let span = request_span(&method, &url);
+        let started = Instant::now();
+        let limit = self.transport.timeouts.request();
+        let result = timed(
+            limit,
+            url.clone(),
+            self.execute(method, url, body, kind, |response| self.read_text(response)),
+        )
+        .instrument(span.clone())
+        .await;
+        span.record("latency_ms", started.elapsed().as_millis() as u64);
+        match result {
+            Ok(body) => {
+                span.record("bytes", body.len());
+                tracing::debug!(parent: &span, "request finished");
+                Ok((body, span))
+            }
+            Err(err) => {
+                tracing::debug!(parent: &span, error = %err, "request failed");
+                Err(err)
+            }
+        }
+    }
+
+    /// Reads a response body, failing if no chunk arrives within the read timeout
+    async fn read_text(&self, response: InFlight) -> Result<String, CloudVisionError> {
+        let url = response.response.url().clone();
+        let limit = self.transport.timeouts.read();
+        let mut chunks = Box::pin(response.bytes_stream());
+        let mut body = Vec::new();
+        while let Some(chunk) = timed(limit, url.clone(), async { Ok(chunks.next().await) }).await?
+        {
+            body.extend_from_slice(chunk?.as_ref());
+        }
+        Ok(String::from_utf8_lossy(&body).into_owned())
+    }
+
+    /// Sends a POST and returns the response once its headers have arrived, leaving the body
+    /// unread
+    async fn send_post(
+        &self,
+        url: Url,
+        body: String,
+        kind: RequestKind,
+    ) -> Result<InFlight, CloudVisionError> {
+        self.execute(Method::POST, url, Some(body), kind, future::ok)
+            .await
+    }
+
+    /// Sends a request and hands the response to `read`, retrying failures the retry policy
+    /// allows for this kind of request. A body that breaks off while `read` consumes it is
+    /// retried like a request that got no response.
+    async fn execute<T, F, Fut>(
+        &self,
+        method: Method,
+        url: Url,
+        body: Option<String>,
+        kind: RequestKind,
+        read: F,
+    ) -> Result<T, CloudVisionError>
+    where
+        F: Fn(InFlight) -> Fut,
+        Fut: Future<Output = Result<T, CloudVisionError>>,
+    {
+        let mut attempt = 1;
+        loop {
+            let (result, retry_after) = self.attempt(method.clone(), url.clone(), &body).await;
+            Span::current().record("retries", attempt - 1);
+            let result = match result {
+                Ok(response) => read(response).await,
+                Err(err) => Err(err),
+            };
+            let err = match result {
+                Ok(value) => return Ok(value),
+                Err(err) => err,
+            };
+            let delay = match self.retry.retry_delay(kind, attempt, &err, retry_after) {
+                Some(delay) => delay,
+                None => return Err(err),
+            };
+            tracing::warn!(
+                error = %err,
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                "retrying request"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Sends an authenticated request and returns the response once its headers have arrived,
+    /// along with any `Retry-After` the server sent. When a token is rejected with a 401 and a
+    /// new one can be fetched, such as by logging in again, the request is repeated once.
+    /// Requests wait for the rate limiter first and count as in flight until their body has
+    /// been read.
+    async fn attempt(
+        &self,
+        method: Method,
+        url: Url,
+        body: &Option<String>,
+    ) -> (Result<InFlight, CloudVisionError>, Option<Duration>) {
+        let mut logged_in_again = false;
+        loop {
+            let token = match self.token().await {
+                Ok(token) => token,
+                Err(err) => return (Err(err), None),
+            };
+            let mut request = self
+                .http
+                .request(method.clone(), url.clone())
+                .header(ACCEPT, "application/json")
+                .bearer_auth(&token);
+            if let Some(body) = body {
+                request = request.body(body.clone());
+            }
+            let sent = match self.dispatch(&method, request).await {
+                Ok(sent) => sent,
+                Err(err) => return (Err(err), None),
+            };
+            let status = sent.response.status();
+            Span::current().record("status", status.as_u16());
+            if status == StatusCode::UNAUTHORIZED && !logged_in_again {
+                let retry = match &self.auth {
+                    Auth::Provider(provider) => provider.invalidate(&token).await,
+                    Auth::Session(session) => {
+                        session.invalidate(&token).await;
+                        true
+                    }
+                };
+                if retry {
+                    logged_in_again = true;
+                    continue;
+                }
+            }
+            let retry_after = retry::retry_after(sent.response.headers());
+            return (sent.check_status().await, retry_after);
+        }
     }
 
-    /// Creates a tag
-    pub async fn create_tag(
+    /// Passes a request through the middleware chain, waits for the rate limiter and returns the
+    /// response once its headers have arrived, within the read timeout
+    async fn dispatch(
         &self,
-        tag_config: TagConfig,
-    ) -> Result<tag::TagConfigResponse, CloudVisionError> {
-        let path = tag::TAG_CONFIG_URL;
-        let json_data = serde_json::to_string(&tag_config)?;
-        let response = self.post(path, json_data).await?;
-        println!("{}", &response);
-        let tcsr = serde_json::from_str(&response)?;
-        Ok(tcsr)
+        method: &Method,
+        request: reqwest::RequestBuilder,
+    ) -> Result<InFlight, CloudVisionError> {
+        let request = self.apply_middleware(request)?;
+        let sent_url = request.url().clone();
+        let permit = self.limiter.acquire().await;
+        let limit = self.transport.timeouts.read();
+        let started = Instant::now();
+        let sent = timed(limit, sent_url.clone(), async {
+            Ok(self.http.execute(request).await?)
+        });
+        let sent = sent.await;
+        let exchange = Exchange::new(method, &sent_url, started.elapsed());
+        match sent {
+            Ok(mut response) => {
+                for layer in self.middleware.iter().rev() {
+                    layer.on_response(&exchange, &mut response);
+                }
+                Ok(InFlight { response, permit })
+            }
+            Err(err) => {
+                for layer in self.middleware.iter().rev() {
+                    layer.on_error(&exchange, &err);
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Posts a login to on-prem CVP and returns the response body. The login is traced, passes
+    /// through the middleware chain and is bounded by the timeouts like any other call, but is
+    /// never retried.
+    async fn login(&self, body: String) -> Result<String, CloudVisionError> {
+        let url = self.build_url(auth::LOGIN_URL);
+        let span = request_span(&Method::POST, &url);
+        let limit = self.transport.timeouts.request();
+        let result = timed(limit, url.clone(), async {
+            let request = self
+                .http
+                .post(url.clone())
+                .header(ACCEPT, "application/json")
+                .header(CONTENT_TYPE, "application/json")
+                .body(body);
+            let sent = self.dispatch(&Method::POST, request).await?;
+            Span::current().record("status", sent.response.status().as_u16());
+            self.read_text(sent.check_status().await?).await
+        })
+        .instrument(span.clone())
+        .await;
+        if let Err(err) = &result {
+            tracing::debug!(parent: &span, error = %err, "login failed");
+        }
+        result
+    }
+
+    /// Builds a request and passes it through the middleware chain in order
+    fn apply_middleware(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Request, CloudVisionError> {
+        let mut request = request.build()?;
+        for layer in &self.middleware {
+            layer.on_request(&mut request)?;
+        }
+        Ok(request)
+    }
+
+    /// The token to authenticate the next request with
+    async fn token(&self) -> Result<String, CloudVisionError> {
+        match &self.auth {
+            Auth::Provider(provider) => provider.token().await,
+            Auth::Session(session) => session.token(|body| self.login(body)).await,
+        }
+    }
+
+    /// Gets the change control with the given id
+    pub fn get_change_control(&self, id: &str) -> Get<changecontrol::ChangeControl> {
+        Get::new(self, vec![("key.id", id.to_owned())])
+    }
+
+    /// Reads change controls, by default all of them
+    pub fn get_change_controls(&self) -> GetAll<changecontrol::ChangeControl> {
+        GetAll::new(self)
+    }
+
+    /// Creates or replaces a change control
+    pub fn update_change_control(
+        &self,
+        config: changecontrol::ChangeConfig,
+    ) -> Call<changecontrol::ChangeUpdate, changecontrol::ChangeControlUpdated> {
+        let update = changecontrol::ChangeUpdate { config };
+        Call::new(
+            self,
+            Method::POST,
+            changecontrol::CHANGE_CONTROL_UPDATE_URL,
+            update,
+        )
+    }
+
+    /// Approves a change control so it can be started
+    pub fn approve_change_control(
+        &self,
+        approval: changecontrol::Approval,
+    ) -> Call<changecontrol::Approval, changecontrol::ChangeControlUpdated> {
+        Call::new(
+            self,
+            Method::POST,
+            changecontrol::CHANGE_CONTROL_APPROVE_URL,
+            approval,
+        )
+    }
+
+    /// Starts an approved change control
+    pub fn execute_change_control(
+        &self,
+        start: changecontrol::StartChange,
+    ) -> Call<changecontrol::StartChange, changecontrol::ChangeControlUpdated> {
+        Call::new(
+            self,
+            Method::POST,
+            changecontrol::CHANGE_CONTROL_START_URL,
+            start,
+        )
+    }
+
+    /// Gets a single device by its id
+    pub fn get_device(&self, device_id: &str) -> Get<device::Device> {
+        Get::new(self, vec![("key.deviceId", device_id.to_owned())])
     }
+
+    /// Reads inventory, by default every device
+    pub fn get_devices(&self) -> GetAll<device::Device> {
+        GetAll::new(self)
+    }
+
+    /// Reads the configurations for tag assignment between network element and tag, by default
+    /// all of them
+    pub fn get_tag_assignment_config(&self) -> GetAll<tag::TagAssignmentConfig> {
+        GetAll::new(self)
+    }
+
+    /// Reads tags, by default all of them
+    pub fn get_tags(&self) -> GetAll<tag::Tag> {
+        GetAll::new(self)
+    }
+
+    /// Streams inventory matching the filter, yielding each device as soon as it arrives rather
+    /// than waiting for the whole response. Same as `get_devices().filters(..).stream()`.
+    pub async fn stream_devices(
+        &self,
+        filter: &Filter<device::DeviceMatch>,
+    ) -> Result<RecordStream<ResourceResponse<device::Device>>, CloudVisionError> {
+        self.get_devices().filters(filter.clone()).stream().await
+    }
+
+    /// Streams tag assignment configurations matching the filter, see `stream_devices`
+    pub async fn stream_tag_assignment_config(
+        &self,
+        filter: &Filter<tag::TagAssignmentMatch>,
+    ) -> Result<RecordStream<ResourceResponse<tag::TagAssignmentConfig>>, CloudVisionError> {
+        self.get_tag_assignment_config()
+            .filters(filter.clone())
+            .stream()
+            .await
+    }
+
+    /// Streams tags matching the filter, see `stream_devices`
+    pub async fn stream_tags(
+        &self,
+        filter: &Filter<tag::TagMatch>,
+    ) -> Result<RecordStream<ResourceResponse<tag::Tag>>, CloudVisionError> {
+        self.get_tags().filters(filter.clone()).stream().await
+    }
+
+    /// Watches inventory matching the filter. Same as `get_devices().filters(..).subscribe()`,
+    /// see `GetAll::subscribe`.
+    pub fn subscribe_devices(
+        &self,
+        filter: &Filter<device::DeviceMatch>,
+    ) -> Result<RecordStream<WatchEvent<ResourceResponse<device::Device>>>, CloudVisionError> {
+        self.get_devices().filters(filter.clone()).subscribe()
+    }
+
+    /// Watches tags matching the filter, see `subscribe_devices`
+    pub fn subscribe_tags(
+        &self,
+        filter: &Filter<tag::TagMatch>,
+    ) -> Result<RecordStream<WatchEvent<ResourceResponse<tag::Tag>>>, CloudVisionError> {
+        self.get_tags().filters(filter.clone()).subscribe()
+    }
+
+    /// Watches tag assignments matching the filter, see `subscribe_devices`
+    pub fn subscribe_tag_assignments(
+        &self,
+        filter: &Filter<tag::TagAssignmentMatch>,
+    ) -> Result<
+        RecordStream<WatchEvent<ResourceResponse<tag::TagAssignmentConfig>>>,
+        CloudVisionError,
+    > {
+        self.get_tag_assignment_config()
+            .filters(filter.clone())
+            .subscribe()
+    }
+
+    /// Creates a tag, its key is set with the setters on `Call<TagConfig, _>`
+    pub fn create_tag(&self) -> Call<TagConfig, ResourceResponse<TagConfig>> {
+        Call::new(
+            self,
+            Method::POST,
+            tag::TAG_CONFIG_URL,
+            TagConfig::default(),
+        )
+    }
+
+    /// POSTs a body and decodes the response body as it arrives
+    pub(crate) async fn open_stream<R>(
+        &self,
+        path: &str,
+        body: String,
+    ) -> Result<RecordStream<R::Record>, CloudVisionError>
+    where
+        R: ServiceResponse,
+        R::Record: Send + 'static,
+    {
+        let url = self.build_url(path);
+        let span = request_span(&Method::POST, &url);
+        let started = Instant::now();
+        let limit = self.transport.timeouts.request();
+        let sent = self.send_post(url.clone(), body, RequestKind::Read);
+        let response = timed(limit, url, sent).instrument(span.clone()).await?;
+        let url = response.response.url().clone();
+        let read = self.transport.timeouts.read();
+        let chunks = stream::counted(response.bytes_stream(), span.clone());
+        let chunks = stream::idle_timeout(chunks, read, url);
+        let records = stream::decode_stream::<R, _, _, _>(chunks);
+        Ok(stream::traced(records, span, started))
+    }
+
+    /// Sends a request to one of the resource API `/all` endpoints and decodes every record,
+    /// keeping the records that could not be decoded
+    pub(crate) async fn fetch_all_lenient<R: ServiceResponse>(
+        &self,
+        url: Url,
+        body: String,
+    ) -> Result<PartialResult<R::Record>, CloudVisionError> {
+        let (response, span) = self
+            .fetch_traced(Method::POST, url, Some(body), RequestKind::Read)
+            .await?;
+        let records = stream::decode_lenient::<R>(&response);
+        span.record("records", records.items().len());
+        Ok(records)
+    }
+}
+
+/// The span a call to CloudVision is traced in. The bearer token is never recorded.
+fn request_span(method: &Method, url: &Url) -> Span {
+    tracing::info_span!(
+        "cloudvision_request",
+        method = %method,
+        path = url.path(),
+        status = Empty,
+        retries = Empty,
+        bytes = Empty,
+        records = Empty,
+        latency_ms = Empty,
+    )
+}
+
+/// Waits for `future`, failing with `Timeout` if `limit` passes first. Dropping the returned
+/// future cancels the request.
+pub(crate) async fn timed<F, T>(
+    limit: Option<Duration>,
+    url: Url,
+    future: F,
+) -> Result<T, CloudVisionError>
+where
+    F: std::future::Future<Output = Result<T, CloudVisionError>>,
+{
+    match limit {
+        Some(limit) => tokio::time::timeout(limit, future)
+            .await
+            .unwrap_or(Err(CloudVisionError::Timeout { url: Some(url) })),
+        None => future.await,
+    }
+}
+
+/// Builds the HTTP client shared by a `Client` and all of its clones
+fn build_http_client(transport: &Transport) -> Result<reqwest::Client, CloudVisionError> {
+    let pool = &transport.pool;
+    let tls = transport
+        .tls
+        .client_config(transport.accept_invalid_certs)?;
+    let mut builder = reqwest::Client::builder()
+        .use_preconfigured_tls(tls)
+        .pool_idle_timeout(pool.idle_timeout())
+        .tcp_keepalive(pool.tcp_keepalive());
+    if let Some(max_idle) = pool.max_idle_per_host {
+        builder = builder.pool_max_idle_per_host(max_idle);
+    }
+    if let Some(connect) = transport.timeouts.connect() {
+        builder = builder.connect_timeout(connect);
+    }
+    if let Some(proxy) = transport.proxy.build()? {
+        builder = builder.proxy(proxy);
+    }
+    Ok(builder.build()?)
 }
 
 /// Config stores the information need to connect to CloudVision
-#[derive(Debug, PartialEq, Deserialize)]
+#[derive(Clone, PartialEq, Deserialize)]
 pub struct Config {
     hostname: String,
     port: Option<u16>,
+    #[serde(default)]
     token: String,
+    token_file: Option<PathBuf>,
+    username: Option<String>,
+    password: Option<String>,
     #[serde(default)]
     accept_invalid_certs: bool,
+    #[serde(default)]
+    pool: PoolConfig,
+    #[serde(default)]
+    tls: TlsConfig,
+    #[serde(default)]
+    proxy: ProxyConfig,
+    #[serde(default)]
+    retry: RetryPolicy,
+    #[serde(default)]
+    rate_limit: RateLimitConfig,
+    #[serde(default)]
+    timeouts: TimeoutConfig,
+}
+
+/// Connection pool settings, read from the `[pool]` table of a config file. Times are in seconds
+/// and may be fractional. Unset values fall back to the defaults of the underlying HTTP client.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+struct PoolConfig {
+    max_idle_per_host: Option<usize>,
+    idle_timeout_secs: Option<f64>,
+    tcp_keepalive_secs: Option<f64>,
+}
+
+impl PoolConfig {
+    /// How long an idle connection is kept in the pool, 90 seconds unless configured
+    fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout_secs
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+            .or(Some(Duration::from_secs(90)))
+    }
+
+    /// How often idle connections are probed, 60 seconds unless configured and off when set to 0.
+    /// Subscriptions have no read timeout, so this is what notices a connection that silently
+    /// went away, such as one dropped by a NAT, and lets the subscription reconnect.
+    fn tcp_keepalive(&self) -> Option<Duration> {
+        match self.tcp_keepalive_secs {
+            None => Some(Duration::from_secs(60)),
+            Some(secs) => Some(secs)
+                .filter(|secs| *secs > 0.0)
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok()),
+        }
+    }
+}
+
+/// Timeouts in seconds, read from the `[timeouts]` table of a config file. By default connecting
+/// may take 10 seconds and the server may go quiet for 30 seconds, with no limit on a whole call.
+/// A value of 0 disables a timeout.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+struct TimeoutConfig {
+    connect_secs: Option<f64>,
+    read_secs: Option<f64>,
+    request_secs: Option<f64>,
+}
+
+impl Default for TimeoutConfig {
+    fn default() -> Self {
+        Self {
+            connect_secs: Some(10.0),
+            read_secs: Some(30.0),
+            request_secs: None,
+        }
+    }
+}
+
+impl TimeoutConfig {
+    /// How long establishing a connection, including the TLS handshake, may take
+    fn connect(&self) -> Option<Duration> {
+        Self::duration(self.connect_secs)
+    }
+
+    /// How long the server may send nothing while a response is awaited or read
+    fn read(&self) -> Option<Duration> {
+        Self::duration(self.read_secs)
+    }
+
+    /// How long a whole call may take
+    fn request(&self) -> Option<Duration> {
+        Self::duration(self.request_secs)
+    }
+
+    fn duration(secs: Option<f64>) -> Option<Duration> {
+        secs.filter(|secs| *secs > 0.0)
+            .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+    }
+}
+
+/// Proxy settings, read from the `[proxy]` table of a config file. Without a proxy url the
+/// standard `HTTPS_PROXY` style environment variables still apply.
+#[derive(Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+struct ProxyConfig {
+    /// `http://`, `https://`, `socks5://` or `socks5h://` url of the proxy
+    url: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+    /// Hosts, domains and IP ranges reached without the proxy
+    no_proxy: Vec<String>,
+}
+
+impl fmt::Debug for ProxyConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProxyConfig")
+            .field("url", &self.url)
+            .field("username", &self.username)
+            .field("password", &self.password.as_deref().map(redact))
+            .field("no_proxy", &self.no_proxy)
+            .finish()
+    }
+}
+
+impl ProxyConfig {
+    /// The proxy every request is sent through, `None` if no proxy url is configured
+    fn build(&self) -> Result<Option<reqwest::Proxy>, CloudVisionError> {
+        let Some(url) = &self.url else {
+            if !self.no_proxy.is_empty() {
+                return Err(CloudVisionError::Proxy(
+                    "no_proxy is set without a proxy url".to_owned(),
+                ));
+            }
+            return Ok(None);
+        };
+        let mut proxy = reqwest::Proxy::all(Url::parse(url)?)?;
+        if let Some(username) = &self.username {
+            proxy = proxy.basic_auth(username, self.password.as_deref().unwrap_or_default());
+        }
+        if !self.no_proxy.is_empty() {
+            proxy = proxy.no_proxy(reqwest::NoProxy::from_string(&self.no_proxy.join(",")));
+        }
+        Ok(Some(proxy))
+    }
+}
+
+/// Secrets are redacted so configs can be logged
+impl fmt::Debug for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Config")
+            .field("hostname", &self.hostname)
+            .field("port", &self.port)
+            .field("token", &redact(&self.token))
+            .field("token_file", &self.token_file)
+            .field("username", &self.username)
+            .field("password", &self.password.as_deref().map(redact))
+            .field("accept_invalid_certs", &self.accept_invalid_certs)
+            .field("pool", &self.pool)
+            .field("tls", &self.tls)
+            .field("proxy", &self.proxy)
+            .field("retry", &self.retry)
+            .field("rate_limit", &self.rate_limit)
+            .field("timeouts", &self.timeouts)
+            .finish()
+    }
+}
+
+/// Stands in for a secret in debug output, empty secrets stay empty so it's clear they're unset
+fn redact(secret: &str) -> &'static str {
+    if secret.is_empty() {
+        ""
+    } else {
+        "<redacted>"
+    }
 }
 
 impl Config {
@@ -208,74 +1289,326 @@ impl Config {
             hostname,
             port,
             token,
+            token_file: None,
+            username: None,
+            password: None,
             accept_invalid_certs: false,
+            pool: PoolConfig::default(),
+            tls: TlsConfig::default(),
+            proxy: ProxyConfig::default(),
+            retry: RetryPolicy::default(),
+            rate_limit: RateLimitConfig::default(),
+            timeouts: TimeoutConfig::default(),
         }
     }
-    /// Builds a configuration from environment variables
-    pub fn from_env() -> Self {
-        let hostname = env::var("CLOUDVISION_HOSTNAME").unwrap();
-        let port = Some(env::var("CLOUDVISION_PORT").unwrap().parse().unwrap());
-        let token = env::var("CLOUDVISION_TOKEN").unwrap();
-        Self::new(hostname, port, token)
+
+    /// Builds a new configuration that reads the token from a file before each request, picking
+    /// up a rotated token as soon as the file changes
+    pub fn with_token_file(hostname: String, port: Option<u16>, path: PathBuf) -> Self {
+        Self {
+            token_file: Some(path),
+            ..Self::new(hostname, port, String::new())
+        }
     }
 
-    /// Builds a configuration reading a file in TOML format
-    pub fn from_file(path: &Path) -> Self {
-        let toml = fs::read_to_string(path).unwrap();
-        toml::from_str(&toml).unwrap()
+    /// Builds a new configuration that logs in to on-prem CVP with a username and password
+    /// instead of using a token
+    pub fn with_credentials(
+        hostname: String,
+        port: Option<u16>,
+        username: String,
+        password: String,
+    ) -> Self {
+        Self {
+            username: Some(username),
+            password: Some(password),
+            ..Self::new(hostname, port, String::new())
+        }
     }
-}
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct PartialEqFilter {
-    partial_eq_filter: Vec<Tag>,
-}
+    /// Sets the maximum number of idle connections kept open per host
+    pub fn set_pool_max_idle_per_host(&mut self, max_idle: usize) {
+        self.pool.max_idle_per_host = Some(max_idle);
+    }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Change {
-    name: String,
-    root_stage_id: String,
-    stages: StageMap,
-    notes: String,
-    time: Option<String>,
-    user: Option<String>,
-}
+    /// Sets how long an idle connection is kept in the pool before it is closed
+    pub fn set_pool_idle_timeout(&mut self, timeout: Duration) {
+        self.pool.idle_timeout_secs = Some(timeout.as_secs_f64());
+    }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct StageMap {
-    values: HashMap<String, Stage>,
-}
+    /// Sets how often TCP keep-alive probes are sent on idle connections, 60 seconds by default.
+    /// `Duration::ZERO` turns them off.
+    pub fn set_tcp_keepalive(&mut self, interval: Duration) {
+        self.pool.tcp_keepalive_secs = Some(interval.as_secs_f64());
+    }
+
+    /// Sets whether the CA certificates installed in the operating system are trusted alongside
+    /// the public roots, which they are by default
+    pub fn set_native_roots(&mut self, native_roots: bool) {
+        self.tls.native_roots = native_roots;
+    }
+
+    /// Trusts the CA certificates in a PEM bundle in addition to the public roots, for clusters
+    /// signed by an internal CA
+    pub fn set_ca_bundle(&mut self, path: PathBuf) {
+        self.tls.ca_bundle = Some(path);
+    }
+
+    /// Presents a client certificate for mTLS, both files are PEM encoded
+    pub fn set_client_identity(&mut self, cert: PathBuf, key: PathBuf) {
+        self.tls.client_cert = Some(cert);
+        self.tls.client_key = Some(key);
+    }
+
+    /// Only accepts a server certificate whose SHA-256 fingerprint is one of the pinned ones.
+    /// Fingerprints are hex, optionally separated by colons.
+    pub fn add_pinned_sha256(&mut self, fingerprint: String) {
+        self.tls.pinned_sha256.push(fingerprint);
+    }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Stage {
-    name: String,
-    action: Action,
-    rows: String,
-    status: StageStatus,
-    error: Option<String>,
+    /// Sends every request through a proxy, the url scheme is `http`, `https`, `socks5` or
+    /// `socks5h` to resolve hostnames through the proxy
+    pub fn set_proxy(&mut self, url: String) {
+        self.proxy.url = Some(url);
+    }
+
+    /// Authenticates to the proxy with basic auth
+    pub fn set_proxy_credentials(&mut self, username: String, password: String) {
+        self.proxy.username = Some(username);
+        self.proxy.password = Some(password);
+    }
+
+    /// Hosts that are reached directly instead of through the proxy, in the same format as the
+    /// `NO_PROXY` environment variable. Only valid together with `set_proxy`.
+    pub fn set_no_proxy(&mut self, hosts: Vec<String>) {
+        self.proxy.no_proxy = hosts;
+    }
+
+    /// Sets how failed requests are retried, see `RetryPolicy`
+    pub fn set_retry_policy(&mut self, retry: RetryPolicy) {
+        self.retry = retry;
+    }
+
+    /// Limits how many requests a client and its clones start per second. Up to `burst` requests
+    /// may start at once after the client has been idle.
+    pub fn set_rate_limit(&mut self, requests_per_second: f64, burst: u32) {
+        self.rate_limit.requests_per_second = Some(requests_per_second);
+        self.rate_limit.burst = Some(burst);
+    }
+
+    /// Sets how long connecting to CloudVision may take
+    pub fn set_connect_timeout(&mut self, timeout: Duration) {
+        self.timeouts.connect_secs = Some(timeout.as_secs_f64());
+    }
+
+    /// Sets how long the server may send nothing while a response is awaited or read.
+    /// Subscriptions are exempt, as they can be quiet for long periods.
+    pub fn set_read_timeout(&mut self, timeout: Duration) {
+        self.timeouts.read_secs = Some(timeout.as_secs_f64());
+    }
+
+    /// Sets how long a whole call may take, including retries and reading the response. Streams
+    /// only have to start within it.
+    pub fn set_request_timeout(&mut self, timeout: Duration) {
+        self.timeouts.request_secs = Some(timeout.as_secs_f64());
+    }
+
+    /// Limits how many requests a client and its clones have in flight at once. A request counts
+    /// until its response body has been read, a stream until it is dropped.
+    pub fn set_max_in_flight(&mut self, max_in_flight: usize) {
+        self.rate_limit.max_in_flight = Some(max_in_flight);
+    }
+    /// Builds a configuration from environment variables. `CLOUDVISION_HOSTNAME` is required along
+    /// with either `CLOUDVISION_TOKEN`, `CLOUDVISION_TOKEN_FILE`, or `CLOUDVISION_USERNAME` and
+    /// `CLOUDVISION_PASSWORD`.
+    /// `CLOUDVISION_PORT`, `CLOUDVISION_ACCEPT_INVALID_CERTS`, `CLOUDVISION_CA_BUNDLE`,
+    /// `CLOUDVISION_CLIENT_CERT` with `CLOUDVISION_CLIENT_KEY`, a comma separated
+    /// `CLOUDVISION_PINNED_SHA256`, `CLOUDVISION_PROXY` with optional `CLOUDVISION_PROXY_USERNAME`
+    /// and `CLOUDVISION_PROXY_PASSWORD`, and a comma separated `CLOUDVISION_NO_PROXY` are
+    /// optional.
+    pub fn from_env() -> Result<Self, CloudVisionError> {
+        Self::from_vars(|name| match env::var(name) {
+            Ok(value) => Ok(Some(value)),
+            Err(env::VarError::NotPresent) => Ok(None),
+            Err(env::VarError::NotUnicode(value)) => Err(CloudVisionError::InvalidEnvVar {
+                name: name.to_owned(),
+                value: value.to_string_lossy().into_owned(),
+            }),
+        })
+    }
+
+    /// Builds a configuration from variables returned by `var`, which returns `None` for unset
+    /// variables
+    fn from_vars<F>(var: F) -> Result<Self, CloudVisionError>
+    where
+        F: Fn(&str) -> Result<Option<String>, CloudVisionError>,
+    {
+        let required =
+            |name: &str| var(name)?.ok_or_else(|| CloudVisionError::MissingEnvVar(name.to_owned()));
+        let hostname = required("CLOUDVISION_HOSTNAME")?;
+        let username = var("CLOUDVISION_USERNAME")?;
+        let token_file = var("CLOUDVISION_TOKEN_FILE")?.map(PathBuf::from);
+        let token = match (&username, &token_file) {
+            (None, None) => required("CLOUDVISION_TOKEN")?,
+            _ => var("CLOUDVISION_TOKEN")?.unwrap_or_default(),
+        };
+        let port = match var("CLOUDVISION_PORT")? {
+            Some(value) => Some(value.parse().map_err(|_| CloudVisionError::InvalidEnvVar {
+                name: "CLOUDVISION_PORT".to_owned(),
+                value,
+            })?),
+            None => None,
+        };
+        let mut config = Self::new(hostname, port, token);
+        config.token_file = token_file;
+        if let Some(username) = username {
+            config.password = Some(required("CLOUDVISION_PASSWORD")?);
+            config.username = Some(username);
+        }
+        if let Some(value) = var("CLOUDVISION_ACCEPT_INVALID_CERTS")? {
+            config.accept_invalid_certs =
+                parse_bool(&value).ok_or_else(|| CloudVisionError::InvalidEnvVar {
+                    name: "CLOUDVISION_ACCEPT_INVALID_CERTS".to_owned(),
+                    value,
+                })?;
+        }
+        config.tls.ca_bundle = var("CLOUDVISION_CA_BUNDLE")?.map(PathBuf::from);
+        if let Some(cert) = var("CLOUDVISION_CLIENT_CERT")? {
+            let key = required("CLOUDVISION_CLIENT_KEY")?;
+            config.set_client_identity(cert.into(), key.into());
+        }
+        if let Some(pins) = var("CLOUDVISION_PINNED_SHA256")? {
+            config.tls.pinned_sha256 = split_list(&pins);
+        }
+        config.proxy.url = var("CLOUDVISION_PROXY")?;
+        if let Some(username) = var("CLOUDVISION_PROXY_USERNAME")? {
+            let password = required("CLOUDVISION_PROXY_PASSWORD")?;
+            config.set_proxy_credentials(username, password);
+        }
+        if let Some(hosts) = var("CLOUDVISION_NO_PROXY")? {
+            config.proxy.no_proxy = split_list(&hosts);
+        }
+        Ok(config)
+    }
+
+    /// Builds a configuration reading a file in TOML format. The profile named by
+    /// `CLOUDVISION_PROFILE` is used when set, otherwise the file's default profile.
+    pub fn from_file(path: &Path) -> Result<Self, CloudVisionError> {
+        let requested = env::var(PROFILE_ENV).ok();
+        Self::read_profile(path, requested.as_deref())
+    }
+
+    /// Builds a configuration from the named profile of a file in TOML format
+    pub fn from_file_profile(path: &Path, name: &str) -> Result<Self, CloudVisionError> {
+        Self::read_profile(path, Some(name))
+    }
+
+    /// Builds a configuration from the named profile of the first config file found on the
+    /// search path, see `search_paths`
+    pub fn from_profile(name: &str) -> Result<Self, CloudVisionError> {
+        Self::from_file_profile(&Self::find_file()?, name)
+    }
+
+    /// Builds a configuration from the first config file found on the search path, using the
+    /// profile named by `CLOUDVISION_PROFILE` when set, otherwise the file's default profile
+    pub fn load() -> Result<Self, CloudVisionError> {
+        Self::from_file(&Self::find_file()?)
+    }
+
+    /// Where config files are looked for, in order: the path in `CLOUDVISION_CONFIG`, then
+    /// `cloudvision/config.toml` in the user's config dir, e.g. `~/.config` on Linux
+    pub fn search_paths() -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+        if let Some(path) = env::var_os(CONFIG_ENV) {
+            paths.push(PathBuf::from(path));
+        }
+        if let Some(dir) = dirs::config_dir() {
+            paths.push(dir.join("cloudvision").join("config.toml"));
+        }
+        paths
+    }
+
+    fn find_file() -> Result<PathBuf, CloudVisionError> {
+        let paths = Self::search_paths();
+        match paths.iter().find(|path| path.is_file()) {
+            Some(path) => Ok(path.clone()),
+            None => Err(CloudVisionError::NoConfigFile(paths)),
+        }
+    }
+
+    /// Reads a profile from a config file. Files without a `[profile.<name>]` table use the flat
+    /// layout and hold a single profile named `default`.
+    fn read_profile(path: &Path, requested: Option<&str>) -> Result<Self, CloudVisionError> {
+        let toml = fs::read_to_string(path).map_err(|source| CloudVisionError::ConfigRead {
+            path: path.to_owned(),
+            source,
+        })?;
+        let parse_error = |source| CloudVisionError::ConfigParse {
+            path: path.to_owned(),
+            source,
+        };
+        let layout: toml::Value = toml::from_str(&toml).map_err(parse_error)?;
+        let mut file = if layout.get("profile").is_some() {
+            toml::from_str::<ConfigFile>(&toml).map_err(parse_error)?
+        } else {
+            let config = toml::from_str::<Config>(&toml).map_err(parse_error)?;
+            ConfigFile {
+                default: None,
+                profile: HashMap::from([(DEFAULT_PROFILE.to_owned(), config)]),
+            }
+        };
+        let name = requested
+            .or(file.default.as_deref())
+            .unwrap_or(DEFAULT_PROFILE)
+            .to_owned();
+        file.profile
+            .remove(&name)
+            .ok_or_else(|| CloudVisionError::UnknownProfile {
+                name,
+                path: path.to_owned(),
+            })
+    }
 }
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Action {
-    name: String,
-    timeout: u32,
-    args: Arg,
+
+/// Environment variable naming the profile to use
+const PROFILE_ENV: &str = "CLOUDVISION_PROFILE";
+/// Environment variable naming a config file to use ahead of the standard location
+const CONFIG_ENV: &str = "CLOUDVISION_CONFIG";
+/// Profile used when neither the environment nor the config file names one
+const DEFAULT_PROFILE: &str = "default";
+
+/// A config file holding one `[profile.<name>]` table per CloudVision cluster, and optionally
+/// the name of the profile to use by default
+#[derive(Debug, Deserialize)]
+struct ConfigFile {
+    default: Option<String>,
+    #[serde(default)]
+    profile: HashMap<String, Config>,
 }
-#[derive(Serialize, Deserialize, Debug)]
-pub struct Arg {
-    values: HashMap<String, String>,
+
+/// Parses the usual spellings of a boolean in an environment variable
+fn parse_bool(value: &str) -> Option<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "true" | "1" | "yes" | "on" => Some(true),
+        "false" | "0" | "no" | "off" | "" => Some(false),
+        _ => None,
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
-pub enum StageStatus {
-    StageStatusUnspecified,
-    StageStatusRunning,
-    StageStatusCompleted,
+/// Splits a comma separated environment variable, dropping empty entries
+fn split_list(value: &str) -> Vec<String> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_owned)
+        .collect()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::testing::{Reply, TestServer};
     struct Setup {
         config: Config,
     }
@@ -301,6 +1634,22 @@ mod tests {
         // TODO some negative cases, bad url, bad port, assigning port to bad url
     }
     #[test]
+    fn test_client_auth() {
+        let config = Config::with_credentials(
+            "cvp.lab.example.com".to_string(),
+            None,
+            "cvpadmin".to_string(),
+            "secret".to_string(),
+        );
+        let client = Client::new(config).unwrap();
+        assert!(matches!(client.auth, Auth::Session(_)));
+        let config = Config::new("cvp.lab.example.com".to_string(), None, String::new());
+        assert!(matches!(
+            Client::new(config).unwrap_err(),
+            CloudVisionError::NoToken
+        ));
+    }
+    #[test]
     fn test_build_url() {
         let base = Setup::new();
         let client = Client::new(base.config).unwrap();
@@ -313,14 +1662,16 @@ mod tests {
     #[tokio::test]
     async fn test_get() {
         let client =
-            Client::new(Config::from_file(Path::new("config/cloudvision.config"))).unwrap();
+            Client::new(Config::from_file(Path::new("config/cloudvision.config")).unwrap())
+                .unwrap();
         let results = client.get("/api/resources/v1/Event/all").await.unwrap();
         assert!(!results.is_empty());
     }
     #[tokio::test]
     async fn test_post() {
         let client =
-            Client::new(Config::from_file(Path::new("config/cloudvision.config"))).unwrap();
+            Client::new(Config::from_file(Path::new("config/cloudvision.config")).unwrap())
+                .unwrap();
         let results = client
             .post("/api/resources/v1/Event/all", "foo".to_string())
             .await
@@ -330,11 +1681,9 @@ mod tests {
     #[tokio::test]
     async fn test_get_all_tag_assignment_config() {
         let client =
-            Client::new(Config::from_file(Path::new("config/cloudvision.config"))).unwrap();
-        let filter = PartialEqFilter {
-            partial_eq_filter: Vec::new(),
-        };
-        let stream = client.get_tag_assignment_config(&filter).await.unwrap();
+            Client::new(Config::from_file(Path::new("config/cloudvision.config")).unwrap())
+                .unwrap();
+        let stream = client.get_tag_assignment_config().send().await.unwrap();
         println!("{:?}", &stream);
         // Using an arbitrary number assuming the demo account has 4 devices at all times
         assert!(!stream.len() > 4);
@@ -342,11 +1691,9 @@ mod tests {
     #[tokio::test]
     async fn test_get_all_devices() {
         let client =
-            Client::new(Config::from_file(Path::new("config/cloudvision.config"))).unwrap();
-        let filter = PartialEqFilter {
-            partial_eq_filter: Vec::new(),
-        };
-        let stream = client.get_devices(&filter).await.unwrap();
+            Client::new(Config::from_file(Path::new("config/cloudvision.config")).unwrap())
+                .unwrap();
+        let stream = client.get_devices().send().await.unwrap();
         // Using an arbitrary number assuming the demo account has 4 devices at all times
         assert!(!stream.len() > 4);
     }
@@ -354,57 +1701,86 @@ mod tests {
     async fn test_get_tags() {
         // Getting everything matching a specific tag
         let client =
-            Client::new(Config::from_file(Path::new("config/cloudvision.config"))).unwrap();
-        let mut tag_key = TagKey::new();
-        tag_key.set_label("router_bgp.as", "65002");
-        let tag = Tag::new(tag_key);
-
-        let filter = PartialEqFilter {
-            partial_eq_filter: vec![tag],
-        };
-        let results = client.get_tags(&filter).await.unwrap();
+            Client::new(Config::from_file(Path::new("config/cloudvision.config")).unwrap())
+                .unwrap();
+        let filter = Filter::<TagMatch>::builder()
+            .label("router_bgp.as")
+            .value("65002")
+            .build();
+        let results = client.get_tags().filters(filter).send().await.unwrap();
         assert!(results.len() == 1);
 
         // Testing bogus label that should return no results
-        let mut tag_key = TagKey::new();
-        tag_key.set_label("foo.as", "65002");
-        let tag = Tag::new(tag_key);
-
-        let filter = PartialEqFilter {
-            partial_eq_filter: vec![tag],
-        };
-        let results = client.get_tags(&filter).await.unwrap();
-        assert!(results.len() == 0);
+        let filter = Filter::<TagMatch>::builder()
+            .label("foo.as")
+            .value("65002")
+            .build();
+        let results = client.get_tags().filters(filter).send().await.unwrap();
+        assert!(results.is_empty());
     }
     #[tokio::test]
     async fn test_get_all_tags() {
         let client =
-            Client::new(Config::from_file(Path::new("config/cloudvision.config"))).unwrap();
-        let results = client.get_all_tags().await.unwrap();
+            Client::new(Config::from_file(Path::new("config/cloudvision.config")).unwrap())
+                .unwrap();
+        let results = client.get_tags().send().await.unwrap();
         println!("there are {} results", results.len());
         assert!(!results.is_empty());
     }
     #[tokio::test]
     async fn test_create_tag() {
         let client =
-            Client::new(Config::from_file(Path::new("config/cloudvision.config"))).unwrap();
-        let mut tag_key = TagKey::new();
-        tag_key.set_workspace_id("8b0eecf4-cd27-430d-ab25-e5aeaca4cf14");
-        tag_key.set_element_type(ElementType::Device);
-        tag_key.set_label("createtag", "foo");
-        let tag_config = TagConfig::new(tag_key, false);
-        let results = client.create_tag(tag_config).await.unwrap();
-        assert!(results.value.key.get_label().unwrap() == "createtag");
+            Client::new(Config::from_file(Path::new("config/cloudvision.config")).unwrap())
+                .unwrap();
+        let results = client
+            .create_tag()
+            .workspace("8b0eecf4-cd27-430d-ab25-e5aeaca4cf14")
+            .element_type(ElementType::Device)
+            .label("createtag", "foo")
+            .send()
+            .await
+            .unwrap();
+        assert!(results.value().key.label() == Some("createtag"));
     }
     #[tokio::test]
     async fn test_get_change_control() {
         let client =
-            Client::new(Config::from_file(Path::new("config/cloudvision.config"))).unwrap();
-        let results = client.get_change_control("").await.unwrap();
-        println!("{:?}", results);
+            Client::new(Config::from_file(Path::new("config/cloudvision.config")).unwrap())
+                .unwrap();
+        let results = client.get_change_controls().send().await.unwrap();
         assert!(!results.is_empty());
     }
     #[test]
+    fn test_error_from_status() {
+        let body = r#"{"code":16,"message":"invalid token","details":[]}"#;
+        let err = CloudVisionError::from_status(StatusCode::UNAUTHORIZED, "/api/x", body);
+        match &err {
+            CloudVisionError::Unauthorized(api) => {
+                assert_eq!(api.status(), StatusCode::UNAUTHORIZED);
+                assert_eq!(api.path(), "/api/x");
+                assert_eq!(api.body().code(), 16);
+                assert_eq!(api.body().message(), "invalid token");
+            }
+            _ => panic!("expected Unauthorized, got {:?}", err),
+        }
+        let err = CloudVisionError::from_status(StatusCode::FORBIDDEN, "/api/x", body);
+        assert!(matches!(err, CloudVisionError::Forbidden(_)));
+        let err = CloudVisionError::from_status(StatusCode::NOT_FOUND, "/api/x", body);
+        assert!(matches!(err, CloudVisionError::NotFound(_)));
+        let err = CloudVisionError::from_status(StatusCode::TOO_MANY_REQUESTS, "/api/x", body);
+        assert!(matches!(err, CloudVisionError::RateLimited(_)));
+        let err = CloudVisionError::from_status(StatusCode::BAD_GATEWAY, "/api/x", "<html>");
+        assert_eq!(err.api_error().unwrap().body().message(), "<html>");
+        assert!(matches!(err, CloudVisionError::Server(_)));
+        let err = CloudVisionError::from_status(
+            StatusCode::BAD_REQUEST,
+            "/api/x",
+            r#"{"error":{"code":3,"message":"bad filter"}}"#,
+        );
+        assert_eq!(err.api_error().unwrap().body().code(), 3);
+        assert!(matches!(err, CloudVisionError::UnexpectedStatus(_)));
+    }
+    #[test]
     fn test_config_new() {
         let config = Config::new(
             "www.cv-staging.arista.io".to_string(),
@@ -418,16 +1794,647 @@ mod tests {
     #[test]
     fn test_config_from_file() {
         let base = Setup::new();
-        let config = Config::from_file(Path::new("test.config"));
+        let config = Config::from_file(Path::new("test.config")).unwrap();
         assert_eq!(config, base.config);
     }
     #[test]
+    fn test_config_tables() {
+        // Each table of a config file sets the same values as the matching setters
+        type Setters = fn(&mut Config);
+        let cases: Vec<(&str, Setters)> = vec![
+            (
+                r#"
+                [pool]
+                max_idle_per_host = 16
+                idle_timeout_secs = 30
+                tcp_keepalive_secs = 0.5
+                "#,
+                |config| {
+                    config.set_pool_max_idle_per_host(16);
+                    config.set_pool_idle_timeout(Duration::from_secs(30));
+                    config.set_tcp_keepalive(Duration::from_millis(500));
+                },
+            ),
+            (
+                r#"
+                [tls]
+                native_roots = false
+                ca_bundle = "/etc/ssl/internal-ca.pem"
+                client_cert = "client.pem"
+                client_key = "client.key"
+                pinned_sha256 = ["AB:CD"]
+                "#,
+                |config| {
+                    config.set_native_roots(false);
+                    config.set_ca_bundle("/etc/ssl/internal-ca.pem".into());
+                    config.set_client_identity("client.pem".into(), "client.key".into());
+                    config.add_pinned_sha256("AB:CD".to_string());
+                },
+            ),
+            (
+                r#"
+                [proxy]
+                url = "socks5h://192.0.2.10:1080"
+                username = "svc"
+                password = "secret"
+                no_proxy = ["localhost", ".corp.example.com"]
+                "#,
+                |config| {
+                    config.set_proxy("socks5h://192.0.2.10:1080".to_string());
+                    config.set_proxy_credentials("svc".to_string(), "secret".to_string());
+                    config.set_no_proxy(vec![
+                        "localhost".to_string(),
+                        ".corp.example.com".to_string(),
+                    ]);
+                },
+            ),
+            (
+                r#"
+                [retry]
+                max_attempts = 5
+                base_delay_ms = 500
+                statuses = [503]
+                errors = ["connect"]
+                retry_writes = true
+                "#,
+                |config| {
+                    config.set_retry_policy(
+                        RetryPolicy::default()
+                            .with_max_attempts(5)
+                            .with_delays(Duration::from_millis(500), Duration::from_secs(10))
+                            .with_statuses(vec![StatusCode::SERVICE_UNAVAILABLE])
+                            .with_errors(vec![retry::RetryOn::Connect])
+                            .with_retry_writes(true),
+                    );
+                },
+            ),
+            (
+                r#"
+                [rate_limit]
+                requests_per_second = 50.0
+                burst = 10
+                max_in_flight = 8
+                "#,
+                |config| {
+                    config.set_rate_limit(50.0, 10);
+                    config.set_max_in_flight(8);
+                },
+            ),
+            (
+                r#"
+                [timeouts]
+                connect_secs = 5
+                read_secs = 0
+                request_secs = 2.5
+                "#,
+                |config| {
+                    config.set_connect_timeout(Duration::from_secs(5));
+                    config.set_read_timeout(Duration::ZERO);
+                    config.set_request_timeout(Duration::from_millis(2500));
+                },
+            ),
+        ];
+        for (table, set) in cases {
+            let file = format!(
+                "hostname = \"www.cv-staging.corp.arista.io\"\nport = 443\ntoken = \"token\"\n{}",
+                table
+            );
+            let config: Config = toml::from_str(&file).unwrap();
+            let mut expected = Setup::new().config;
+            set(&mut expected);
+            assert_eq!(config, expected, "{}", table);
+        }
+    }
+    #[test]
+    fn test_pool_sub_second() {
+        let mut config = Setup::new().config;
+        assert_eq!(config.pool.idle_timeout(), Some(Duration::from_secs(90)));
+        assert_eq!(config.pool.tcp_keepalive(), Some(Duration::from_secs(60)));
+        config.set_pool_idle_timeout(Duration::from_millis(500));
+        config.set_tcp_keepalive(Duration::from_millis(1500));
+        assert_eq!(config.pool.idle_timeout(), Some(Duration::from_millis(500)));
+        assert_eq!(
+            config.pool.tcp_keepalive(),
+            Some(Duration::from_millis(1500))
+        );
+        config.set_tcp_keepalive(Duration::ZERO);
+        assert_eq!(config.pool.tcp_keepalive(), None);
+    }
+    #[test]
+    fn test_client_settings() {
+        // A malformed pin is only rejected when the client is built
+        let mut config = Setup::new().config;
+        config.add_pinned_sha256("AB:CD".to_string());
+        assert!(matches!(Client::new(config), Err(CloudVisionError::Tls(_))));
+
+        assert!(ProxyConfig::default().build().unwrap().is_none());
+        let mut config = Setup::new().config;
+        config.set_proxy("socks5h://192.0.2.10:1080".to_string());
+        assert!(config.proxy.build().unwrap().is_some());
+        assert!(Client::new(config).is_ok());
+        let mut config = Setup::new().config;
+        config.set_proxy("not a url".to_string());
+        assert!(matches!(
+            Client::new(config),
+            Err(CloudVisionError::UrlParse(_))
+        ));
+        let mut config = Setup::new().config;
+        config.set_no_proxy(vec!["localhost".to_string()]);
+        assert!(matches!(
+            Client::new(config),
+            Err(CloudVisionError::Proxy(_))
+        ));
+
+        let mut config = Setup::new().config;
+        config.set_read_timeout(Duration::ZERO);
+        assert_eq!(config.timeouts.read(), None);
+        assert_eq!(config.timeouts.connect(), Some(Duration::from_secs(10)));
+    }
+    #[tokio::test]
+    async fn test_rate_limit_waits() {
+        let server = TestServer::start(vec![Reply::new(200, "{}"); 3]).await;
+        let mut config = Setup::new().config;
+        config.set_rate_limit(10.0, 1);
+        let client = server.client_with(config);
+        let started = Instant::now();
+        for _ in 0..3 {
+            client
+                .get_json::<serde_json::Value>("/api/x", &[])
+                .await
+                .unwrap();
+        }
+        // The first request uses the burst, the other two wait 100ms each
+        assert!(started.elapsed() >= Duration::from_millis(180));
+    }
+    #[tokio::test]
+    async fn test_max_in_flight() {
+        let reply = Reply::new(200, "{}").delay(Duration::from_millis(200));
+        let server = TestServer::start(vec![reply; 2]).await;
+        let mut config = Setup::new().config;
+        config.set_max_in_flight(1);
+        let client = server.client_with(config.clone());
+        let clone = client.clone();
+        let started = Instant::now();
+        let (first, second) = tokio::join!(
+            client.get_json::<serde_json::Value>("/api/x", &[]),
+            clone.get_json::<serde_json::Value>("/api/x", &[]),
+        );
+        first.unwrap();
+        second.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(400));
+
+        // An open stream holds its place until it is dropped, not just until its headers arrive
+        let server = TestServer::start(vec![Reply::new(200, ""), Reply::new(200, "{}")]).await;
+        let client = server.client_with(config);
+        let clone = client.clone();
+        let devices = client.get_devices().stream().await.unwrap();
+        let second = clone.get_json::<serde_json::Value>("/api/x", &[]);
+        tokio::pin!(second);
+        let waited = tokio::time::timeout(Duration::from_millis(100), &mut second).await;
+        assert!(waited.is_err());
+        assert_eq!(server.received().len(), 1);
+        drop(devices);
+        second.await.unwrap();
+    }
+    #[tokio::test]
+    async fn test_timeouts_fire() {
+        let slow = Reply::new(200, "{}").delay(Duration::from_secs(5));
+        let server = TestServer::start(vec![slow.clone(), slow]).await;
+        let mut config = Setup::new().config;
+        config.set_retry_policy(RetryPolicy::none());
+        config.set_read_timeout(Duration::from_millis(100));
+        let client = server.client_with(config);
+        let started = Instant::now();
+        let err = client
+            .get_json::<serde_json::Value>("/api/x", &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CloudVisionError::Timeout { .. }));
+
+        // The request timeout bounds the whole call even when the read timeout is longer
+        let client = client
+            .with_read_timeout(Duration::from_secs(30))
+            .with_timeout(Duration::from_millis(100));
+        let err = client
+            .get_json::<serde_json::Value>("/api/x", &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CloudVisionError::Timeout { .. }));
+        assert!(started.elapsed() < Duration::from_secs(2));
+    }
+    #[tokio::test]
+    async fn test_retry_loop() {
+        let server = TestServer::start(vec![
+            Reply::new(503, "{}"),
+            Reply::new(502, "{}"),
+            Reply::new(200, r#"{"ok":true}"#),
+            Reply::new(503, "{}"),
+        ])
+        .await;
+        let mut config = Setup::new().config;
+        config.set_retry_policy(
+            RetryPolicy::default()
+                .with_jitter(false)
+                .with_delays(Duration::from_millis(10), Duration::from_millis(50)),
+        );
+        let client = server.client_with(config);
+        let response: serde_json::Value = client.get_json("/api/x", &[]).await.unwrap();
+        assert_eq!(response["ok"], true);
+        assert_eq!(server.received().len(), 3);
+
+        // Writes are not retried by default
+        let err = client
+            .post_json::<_, serde_json::Value>("/api/x", &serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CloudVisionError::Server(_)));
+        assert_eq!(server.received().len(), 4);
+    }
+    #[tokio::test]
+    async fn test_retry_body() {
+        let body = r#"{"ok":true}"#;
+        let server = TestServer::start(vec![
+            Reply::new(200, body).cut_after(4),
+            Reply::new(200, body),
+            Reply::new(200, body).cut_after(4),
+        ])
+        .await;
+        let mut config = Setup::new().config;
+        config.set_retry_policy(
+            RetryPolicy::default()
+                .with_jitter(false)
+                .with_delays(Duration::from_millis(10), Duration::from_millis(50)),
+        );
+        let client = server.client_with(config);
+        let response: serde_json::Value = client.get_json("/api/x", &[]).await.unwrap();
+        assert_eq!(response["ok"], true);
+        assert_eq!(server.received().len(), 2);
+
+        // A write whose response broke off may have been applied, so it is not repeated
+        let err = client
+            .post_json::<_, serde_json::Value>("/api/x", &serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CloudVisionError::Request(_)));
+        assert_eq!(server.received().len(), 3);
+    }
+    #[tokio::test]
+    async fn test_timed() {
+        let url = Url::parse("https://cv.example.com/api").unwrap();
+        let pending = futures::future::pending::<Result<(), CloudVisionError>>();
+        let err = timed(Some(Duration::from_millis(10)), url.clone(), pending)
+            .await
+            .unwrap_err();
+        assert!(matches!(&err, CloudVisionError::Timeout { url: Some(u) } if *u == url));
+        assert!(err.is_transient());
+        let ready = async { Ok(1) };
+        assert_eq!(timed(None, url, ready).await.unwrap(), 1);
+    }
+    #[tokio::test]
+    async fn test_retry_after() {
+        let server = TestServer::start(vec![
+            Reply::new(503, "{}").header("retry-after", "0"),
+            Reply::new(200, r#"{"ok":true}"#),
+            Reply::new(429, "{}").header("retry-after", "3600"),
+        ])
+        .await;
+        let client = server.client();
+        let response: serde_json::Value = client.get_json("/api/x", &[]).await.unwrap();
+        assert_eq!(response["ok"], true);
+        // A Retry-After beyond the maximum delay fails straight away instead of sleeping
+        let started = Instant::now();
+        let err = client
+            .get_json::<serde_json::Value>("/api/x", &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CloudVisionError::RateLimited(_)));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(server.received().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_session_login_again_after_401() {
+        let server = TestServer::start(vec![
+            Reply::new(200, r#"{"sessionId":"first"}"#),
+            Reply::new(401, r#"{"code":16,"message":"session expired"}"#),
+            Reply::new(200, r#"{"sessionId":"second"}"#),
+            Reply::new(200, r#"{"ok":true}"#),
+        ])
+        .await;
+        let config = Config::with_credentials(
+            String::new(),
+            None,
+            "cvpadmin".to_string(),
+            "secret".to_string(),
+        );
+        let client = server.client_with(config);
+        let response: serde_json::Value = client.get_json("/api/x", &[]).await.unwrap();
+        assert_eq!(response["ok"], true);
+        let received = server.received();
+        let lines: Vec<_> = received.iter().map(|request| request.line()).collect();
+        assert_eq!(
+            lines,
+            [
+                "POST /cvpservice/login/authenticate.do",
+                "GET /api/x",
+                "POST /cvpservice/login/authenticate.do",
+                "GET /api/x"
+            ]
+        );
+        assert_eq!(received[1].header("authorization"), Some("Bearer first"));
+        assert_eq!(received[3].header("authorization"), Some("Bearer second"));
+    }
+
+    #[tokio::test]
+    async fn test_session_gives_up_after_second_401() {
+        let server = TestServer::start(vec![
+            Reply::new(200, r#"{"sessionId":"first"}"#),
+            Reply::new(401, "{}"),
+            Reply::new(200, r#"{"sessionId":"second"}"#),
+            Reply::new(401, "{}"),
+        ])
+        .await;
+        let config = Config::with_credentials(
+            String::new(),
+            None,
+            "cvpadmin".to_string(),
+            "secret".to_string(),
+        );
+        let client = server.client_with(config);
+        let err = client
+            .get_json::<serde_json::Value>("/api/x", &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CloudVisionError::Unauthorized(_)));
+        assert_eq!(server.received().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_login_timeout() {
+        let server = TestServer::start(vec![
+            Reply::new(200, r#"{"sessionId":"first"}"#).delay(Duration::from_secs(5))
+        ])
+        .await;
+        let mut config = Config::with_credentials(
+            String::new(),
+            None,
+            "cvpadmin".to_string(),
+            "secret".to_string(),
+        );
+        config.set_read_timeout(Duration::from_millis(100));
+        config.set_retry_policy(RetryPolicy::none());
+        let client = server.client_with(config);
+        let err = client
+            .get_json::<serde_json::Value>("/api/x", &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(err, CloudVisionError::Timeout { .. }));
+        // The login passed through the middleware chain
+        assert_eq!(
+            server.received()[0].line(),
+            "POST /cvpservice/login/authenticate.do"
+        );
+    }
+
+    #[test]
+    fn test_query_url() {
+        let client = Client::new(Setup::new().config).unwrap();
+        let url = client.query_url(tag::TAG_CONFIG_URL, &[]);
+        assert_eq!(url.query(), None);
+        let url = client.query_url(
+            tag::TAG_CONFIG_URL,
+            &[("key.label", "site"), ("time", "2022-02-10T21:43:03Z")],
+        );
+        assert_eq!(
+            url.query(),
+            Some("key.label=site&time=2022-02-10T21%3A43%3A03Z")
+        );
+    }
+    #[test]
+    fn test_middleware_chain() {
+        #[derive(Debug)]
+        struct Proxied;
+        impl Middleware for Proxied {
+            fn on_request(&self, request: &mut reqwest::Request) -> Result<(), CloudVisionError> {
+                let path = format!("/cvp{}", request.url().path());
+                request.url_mut().set_path(&path);
+                Ok(())
+            }
+        }
+        let mut client = Client::new(Setup::new().config).unwrap();
+        client.add_middleware(Arc::new(
+            middleware::UserAgent::new("tag-sync/1.2").unwrap(),
+        ));
+        client.add_middleware(Arc::new(Proxied));
+        let url = client.build_url(device::DEVICE_URL);
+        let request = client
+            .apply_middleware(client.http.get(url).bearer_auth("token"))
+            .unwrap();
+        assert_eq!(request.url().path(), format!("/cvp{}", device::DEVICE_URL));
+        assert_eq!(request.headers()["user-agent"], "tag-sync/1.2");
+        assert_eq!(request.headers()["authorization"], "Bearer token");
+    }
+    #[tokio::test]
+    async fn test_config_debug_redacts_secrets() {
+        let mut config = Config::with_credentials(
+            "cvp.lab.example.com".to_string(),
+            None,
+            "cvpadmin".to_string(),
+            "hunter2".to_string(),
+        );
+        config.token = "eyJhbGciOiJFUzI1NiJ9".to_string();
+        config.set_proxy("http://proxy:3128".to_string());
+        config.set_proxy_credentials("svc".to_string(), "proxy-secret".to_string());
+        let debug = format!("{:?}", config);
+        assert!(debug.contains("cvpadmin"));
+        for secret in ["hunter2", "eyJhbGciOiJFUzI1NiJ9", "proxy-secret"] {
+            assert!(!debug.contains(secret), "{} leaked in {}", secret, debug);
+        }
+        let client = Client::new(config).unwrap();
+        assert!(!format!("{:?}", client).contains("eyJhbGciOiJFUzI1NiJ9"));
+
+        // Providers that cached a token print neither the token nor the cache
+        let path = env::temp_dir().join("cloudvision-debug-token.txt");
+        std::fs::write(&path, "file-secret\n").unwrap();
+        let provider = TokenFile::new(path.clone());
+        assert_eq!(provider.token().await.unwrap(), "file-secret");
+        let debug = format!("{:?}", provider);
+        std::fs::remove_file(&path).unwrap();
+        assert!(debug.contains("cloudvision-debug-token.txt"));
+        assert!(!debug.contains("file-secret"), "token leaked in {}", debug);
+        // The arguments are shown, so the command computes the token instead of echoing it
+        let provider = crate::auth::CommandToken::new(
+            "sh".to_string(),
+            vec!["-c".to_string(), "echo command-$((40 + 2))".to_string()],
+        );
+        assert_eq!(provider.token().await.unwrap(), "command-42");
+        let debug = format!("{:?}", provider);
+        assert!(debug.contains("\"sh\""));
+        assert!(!debug.contains("command-42"), "token leaked in {}", debug);
+    }
+    #[test]
     fn test_config_from_env() {
         let base = Setup::new();
         env::set_var("CLOUDVISION_HOSTNAME", "www.cv-staging.corp.arista.io");
         env::set_var("CLOUDVISION_PORT", "443");
         env::set_var("CLOUDVISION_TOKEN", "token");
-        let config = Config::from_env();
+        let config = Config::from_env().unwrap();
         assert_eq!(config, base.config);
     }
+    #[test]
+    fn test_config_from_vars() {
+        let vars = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                Ok(vars
+                    .iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string()))
+            }
+        };
+        let config = Config::from_vars(vars(&[
+            ("CLOUDVISION_HOSTNAME", "www.cv-staging.corp.arista.io"),
+            ("CLOUDVISION_TOKEN", "token"),
+            ("CLOUDVISION_ACCEPT_INVALID_CERTS", "true"),
+        ]))
+        .unwrap();
+        assert_eq!(config.port, None);
+        assert!(config.accept_invalid_certs);
+
+        let config = Config::from_vars(vars(&[
+            ("CLOUDVISION_HOSTNAME", "cvp.lab.example.com"),
+            ("CLOUDVISION_USERNAME", "cvpadmin"),
+            ("CLOUDVISION_PASSWORD", "secret"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            config,
+            Config::with_credentials(
+                "cvp.lab.example.com".to_string(),
+                None,
+                "cvpadmin".to_string(),
+                "secret".to_string()
+            )
+        );
+        let err = Config::from_vars(vars(&[
+            ("CLOUDVISION_HOSTNAME", "cv"),
+            ("CLOUDVISION_USERNAME", "cvpadmin"),
+        ]))
+        .unwrap_err();
+        assert!(
+            matches!(err, CloudVisionError::MissingEnvVar(name) if name == "CLOUDVISION_PASSWORD")
+        );
+
+        let config = Config::from_vars(vars(&[
+            ("CLOUDVISION_HOSTNAME", "cv"),
+            ("CLOUDVISION_TOKEN", "token"),
+            ("CLOUDVISION_CA_BUNDLE", "ca.pem"),
+            ("CLOUDVISION_PINNED_SHA256", "AA, BB"),
+        ]))
+        .unwrap();
+        assert_eq!(config.tls.ca_bundle, Some("ca.pem".into()));
+        assert_eq!(config.tls.pinned_sha256, vec!["AA", "BB"]);
+
+        let config = Config::from_vars(vars(&[
+            ("CLOUDVISION_HOSTNAME", "cv"),
+            ("CLOUDVISION_TOKEN", "token"),
+            ("CLOUDVISION_PROXY", "http://proxy:3128"),
+            ("CLOUDVISION_PROXY_USERNAME", "svc"),
+            ("CLOUDVISION_PROXY_PASSWORD", "secret"),
+            ("CLOUDVISION_NO_PROXY", "localhost,,10.0.0.0/8"),
+        ]))
+        .unwrap();
+        let mut expected = Config::new("cv".to_string(), None, "token".to_string());
+        expected.set_proxy("http://proxy:3128".to_string());
+        expected.set_proxy_credentials("svc".to_string(), "secret".to_string());
+        expected.set_no_proxy(vec!["localhost".to_string(), "10.0.0.0/8".to_string()]);
+        assert_eq!(config, expected);
+        let err = Config::from_vars(vars(&[
+            ("CLOUDVISION_HOSTNAME", "cv"),
+            ("CLOUDVISION_TOKEN", "token"),
+            ("CLOUDVISION_CLIENT_CERT", "client.pem"),
+        ]))
+        .unwrap_err();
+        assert!(
+            matches!(err, CloudVisionError::MissingEnvVar(name) if name == "CLOUDVISION_CLIENT_KEY")
+        );
+
+        let config = Config::from_vars(vars(&[
+            ("CLOUDVISION_HOSTNAME", "cv"),
+            ("CLOUDVISION_TOKEN_FILE", "/run/secrets/cv-token"),
+        ]))
+        .unwrap();
+        assert_eq!(
+            config,
+            Config::with_token_file("cv".to_string(), None, "/run/secrets/cv-token".into())
+        );
+
+        let err = Config::from_vars(vars(&[("CLOUDVISION_HOSTNAME", "cv")])).unwrap_err();
+        assert!(
+            matches!(err, CloudVisionError::MissingEnvVar(name) if name == "CLOUDVISION_TOKEN")
+        );
+
+        let err = Config::from_vars(vars(&[
+            ("CLOUDVISION_HOSTNAME", "cv"),
+            ("CLOUDVISION_TOKEN", "token"),
+            ("CLOUDVISION_PORT", "https"),
+        ]))
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            CloudVisionError::InvalidEnvVar { name, value }
+                if name == "CLOUDVISION_PORT" && value == "https"
+        ));
+    }
+    #[test]
+    fn test_config_profiles() {
+        let path = Path::new("test.config");
+        let lab = Config::from_file_profile(path, "lab").unwrap();
+        let mut expected = Config::new("cvp.lab.example.com".to_string(), None, "lab".to_string());
+        expected.accept_invalid_certs = true;
+        assert_eq!(lab, expected);
+        let err = Config::from_file_profile(path, "missing").unwrap_err();
+        assert!(matches!(err, CloudVisionError::UnknownProfile { name, .. } if name == "missing"));
+
+        // The default key picks the profile, flat files hold a single default profile
+        let dir = env::temp_dir();
+        let named = dir.join("cloudvision-named-default.toml");
+        fs::write(
+            &named,
+            "default = \"lab\"\n[profile.lab]\nhostname = \"cv\"\ntoken = \"token\"\n",
+        )
+        .unwrap();
+        let config = Config::read_profile(&named, None).unwrap();
+        assert_eq!(config.hostname, "cv");
+        fs::remove_file(&named).unwrap();
+        let flat = dir.join("cloudvision-flat.toml");
+        fs::write(&flat, "hostname = \"cv\"\ntoken = \"token\"\n").unwrap();
+        assert_eq!(Config::read_profile(&flat, None).unwrap().hostname, "cv");
+        assert!(Config::read_profile(&flat, Some("lab")).is_err());
+        fs::remove_file(&flat).unwrap();
+    }
+    #[test]
+    fn test_config_from_file_errors() {
+        let err = Config::from_file(Path::new("does/not/exist.config")).unwrap_err();
+        assert!(matches!(err, CloudVisionError::ConfigRead { .. }));
+        assert!(err.to_string().contains("does/not/exist.config"));
+
+        let path = env::temp_dir().join("cloudvision-bad-config.toml");
+        fs::write(
+            &path,
+            "hostname = \"cv\"\nport = \"https\"\ntoken = \"token\"\n",
+        )
+        .unwrap();
+        let err = Config::from_file(&path).unwrap_err();
+        match &err {
+            CloudVisionError::ConfigParse {
+                path: err_path,
+                source,
+            } => {
+                assert_eq!(err_path, &path);
+                assert_eq!(source.line_col().map(|(line, _)| line), Some(1));
+            }
+            _ => panic!("expected a parse error, got {:?}", err),
+        }
+        fs::remove_file(&path).unwrap();
+    }
 }
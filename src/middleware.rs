@@ -0,0 +1,212 @@
+use crate::CloudVisionError;
+use reqwest::header::{HeaderName, HeaderValue, USER_AGENT};
+use reqwest::{Method, Request, Response};
+use std::fmt;
+use std::time::Duration;
+use url::Url;
+
+/// A request that was sent, passed to the response and error hooks of a `Middleware`
+#[derive(Debug)]
+pub struct Exchange<'a> {
+    method: &'a Method,
+    url: &'a Url,
+    elapsed: Duration,
+}
+
+impl<'a> Exchange<'a> {
+    pub(crate) fn new(method: &'a Method, url: &'a Url, elapsed: Duration) -> Self {
+        Self {
+            method,
+            url,
+            elapsed,
+        }
+    }
+
+    pub fn method(&self) -> &Method {
+        self.method
+    }
+
+    /// The url the request was sent to, after every middleware had a chance to rewrite it
+    pub fn url(&self) -> &Url {
+        self.url
+    }
+
+    /// How long it took for the response headers, or the error, to arrive
+    pub fn elapsed(&self) -> Duration {
+        self.elapsed
+    }
+}
+
+/// Hooks that run around every request a `Client` sends, including retries and the typed API
+/// helpers. Requests pass through the chain in the order the middleware was added, responses and
+/// errors in reverse order. The request already carries the bearer token, so middleware that
+/// logs headers must leave `Authorization` out.
+pub trait Middleware: Send + Sync + fmt::Debug {
+    /// Inspects or changes a request before it is sent, such as adding headers or rewriting the
+    /// url. Returning an error fails the call without sending the request.
+    fn on_request(&self, _request: &mut Request) -> Result<(), CloudVisionError> {
+        Ok(())
+    }
+
+    /// Inspects or changes a response once its headers have arrived, before its status is
+    /// checked
+    fn on_response(&self, _exchange: &Exchange<'_>, _response: &mut Response) {}
+
+    /// Observes a request that failed without a response
+    fn on_error(&self, _exchange: &Exchange<'_>, _err: &CloudVisionError) {}
+}
+
+/// Sets the `User-Agent` header of every request
+#[derive(Debug, Clone)]
+pub struct UserAgent(HeaderValue);
+
+impl UserAgent {
+    pub fn new(user_agent: &str) -> Result<Self, CloudVisionError> {
+        HeaderValue::from_str(user_agent)
+            .map(UserAgent)
+            .map_err(|_| CloudVisionError::InvalidHeader(user_agent.to_owned()))
+    }
+}
+
+impl Middleware for UserAgent {
+    fn on_request(&self, request: &mut Request) -> Result<(), CloudVisionError> {
+        request.headers_mut().insert(USER_AGENT, self.0.clone());
+        Ok(())
+    }
+}
+
+/// Gives every request a random id in the `x-request-id` header, or another header, so it can be
+/// correlated with server and proxy logs. Requests that already carry the header keep it.
+#[derive(Debug, Clone)]
+pub struct RequestId {
+    header: HeaderName,
+}
+
+impl Default for RequestId {
+    fn default() -> Self {
+        Self {
+            header: HeaderName::from_static("x-request-id"),
+        }
+    }
+}
+
+impl RequestId {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends the id in `header` instead of `x-request-id`
+    pub fn with_header(header: &str) -> Result<Self, CloudVisionError> {
+        let header = HeaderName::from_bytes(header.as_bytes())
+            .map_err(|_| CloudVisionError::InvalidHeader(header.to_owned()))?;
+        Ok(Self { header })
+    }
+}
+
+impl Middleware for RequestId {
+    fn on_request(&self, request: &mut Request) -> Result<(), CloudVisionError> {
+        if !request.headers().contains_key(&self.header) {
+            let id = format!("{:032x}", fastrand::u128(..));
+            let value = HeaderValue::from_str(&id).expect("hex is a valid header value");
+            request.headers_mut().insert(self.header.clone(), value);
+        }
+        Ok(())
+    }
+}
+
+/// Logs every request and its outcome as `tracing` events, without headers or bodies
+#[derive(Debug, Clone, Default)]
+pub struct Logging;
+
+impl Middleware for Logging {
+    fn on_request(&self, request: &mut Request) -> Result<(), CloudVisionError> {
+        tracing::info!(method = %request.method(), url = %request.url(), "sending request");
+        Ok(())
+    }
+
+    fn on_response(&self, exchange: &Exchange<'_>, response: &mut Response) {
+        tracing::info!(
+            method = %exchange.method(),
+            url = %exchange.url(),
+            status = response.status().as_u16(),
+            elapsed_ms = exchange.elapsed().as_millis() as u64,
+            "received response"
+        );
+    }
+
+    fn on_error(&self, exchange: &Exchange<'_>, err: &CloudVisionError) {
+        tracing::warn!(
+            method = %exchange.method(),
+            url = %exchange.url(),
+            elapsed_ms = exchange.elapsed().as_millis() as u64,
+            error = %err,
+            "request failed"
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request() -> Request {
+        Request::new(
+            Method::GET,
+            Url::parse("https://cv.example.com/api/resources/inventory/v1/Device/all").unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_user_agent() {
+        let mut request = request();
+        UserAgent::new("tag-sync/1.2")
+            .unwrap()
+            .on_request(&mut request)
+            .unwrap();
+        assert_eq!(request.headers()[USER_AGENT], "tag-sync/1.2");
+        assert!(matches!(
+            UserAgent::new("bad\nagent"),
+            Err(CloudVisionError::InvalidHeader(_))
+        ));
+    }
+
+    #[test]
+    fn test_request_id() {
+        let layer = RequestId::new();
+        let mut first = request();
+        let mut second = request();
+        layer.on_request(&mut first).unwrap();
+        layer.on_request(&mut second).unwrap();
+        let id = first.headers()["x-request-id"].to_str().unwrap().to_owned();
+        assert_eq!(id.len(), 32);
+        assert_ne!(second.headers()["x-request-id"], id.as_str());
+
+        // A caller supplied id is kept
+        layer.on_request(&mut first).unwrap();
+        assert_eq!(first.headers()["x-request-id"], id.as_str());
+
+        let mut request = request();
+        let layer = RequestId::with_header("X-Correlation-ID").unwrap();
+        layer.on_request(&mut request).unwrap();
+        assert!(request.headers().contains_key("x-correlation-id"));
+    }
+
+    #[test]
+    fn test_rewrite_path() {
+        #[derive(Debug)]
+        struct Prefix;
+        impl Middleware for Prefix {
+            fn on_request(&self, request: &mut Request) -> Result<(), CloudVisionError> {
+                let path = format!("/cvp{}", request.url().path());
+                request.url_mut().set_path(&path);
+                Ok(())
+            }
+        }
+        let mut request = request();
+        Prefix.on_request(&mut request).unwrap();
+        assert_eq!(
+            request.url().path(),
+            "/cvp/api/resources/inventory/v1/Device/all"
+        );
+    }
+}
@@ -0,0 +1,133 @@
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// Client side limits, read from the `[rate_limit]` table of a config file. Unset values don't
+/// limit anything.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default)]
+pub(crate) struct RateLimitConfig {
+    /// Requests started per second, averaged over time
+    pub(crate) requests_per_second: Option<f64>,
+    /// Requests that may start at once after an idle period, defaults to one second's worth
+    pub(crate) burst: Option<u32>,
+    /// Requests in flight at any one time, until their response body has been read
+    pub(crate) max_in_flight: Option<usize>,
+}
+
+/// Applies a `RateLimitConfig` to every request of a `Client` and its clones
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    bucket: Option<Mutex<TokenBucket>>,
+    in_flight: Option<Arc<Semaphore>>,
+}
+
+impl RateLimiter {
+    pub(crate) fn new(config: &RateLimitConfig) -> Self {
+        let bucket = config
+            .requests_per_second
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| {
+                let burst = config.burst.unwrap_or(rate.ceil() as u32).max(1);
+                Mutex::new(TokenBucket::new(rate, burst))
+            });
+        Self {
+            bucket,
+            in_flight: config
+                .max_in_flight
+                .map(|max| Arc::new(Semaphore::new(max.max(1)))),
+        }
+    }
+
+    /// Waits until a request may start. The returned permit counts the request as in flight until
+    /// it is dropped.
+    pub(crate) async fn acquire(&self) -> Option<OwnedSemaphorePermit> {
+        let permit = match &self.in_flight {
+            Some(in_flight) => Some(
+                in_flight
+                    .clone()
+                    .acquire_owned()
+                    .await
+                    .expect("rate limiter semaphore is never closed"),
+            ),
+            None => None,
+        };
+        if let Some(bucket) = &self.bucket {
+            let wait = bucket.lock().await.reserve();
+            tokio::time::sleep(wait).await;
+        }
+        permit
+    }
+}
+
+/// Tokens refill continuously at `rate` per second up to `burst`. Each request takes one, and
+/// callers that find the bucket empty reserve a token ahead of time and wait for it, so waiters
+/// are served in order.
+#[derive(Debug)]
+struct TokenBucket {
+    rate: f64,
+    burst: f64,
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(rate: f64, burst: u32) -> Self {
+        Self {
+            rate,
+            burst: burst.into(),
+            tokens: burst.into(),
+            updated: Instant::now(),
+        }
+    }
+
+    /// Takes a token and returns how long to wait before it is available
+    fn reserve(&mut self) -> Duration {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.burst);
+        self.updated = now;
+        self.tokens -= 1.0;
+        if self.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let mut bucket = TokenBucket::new(10.0, 2);
+        assert_eq!(bucket.reserve(), Duration::ZERO);
+        assert_eq!(bucket.reserve(), Duration::ZERO);
+        let wait = bucket.reserve();
+        assert!(wait > Duration::from_millis(90) && wait <= Duration::from_millis(100));
+        // Later callers queue behind the reserved token
+        assert!(bucket.reserve() > Duration::from_millis(190));
+    }
+
+    #[tokio::test]
+    async fn test_max_in_flight() {
+        let limiter = RateLimiter::new(&RateLimitConfig {
+            max_in_flight: Some(1),
+            ..RateLimitConfig::default()
+        });
+        let permit = limiter.acquire().await;
+        assert!(permit.is_some());
+        let blocked = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(blocked.is_err());
+        drop(permit);
+        let unblocked = tokio::time::timeout(Duration::from_millis(20), limiter.acquire()).await;
+        assert!(unblocked.is_ok());
+
+        let unlimited = RateLimiter::new(&RateLimitConfig::default());
+        assert!(unlimited.acquire().await.is_none());
+    }
+}
@@ -0,0 +1,416 @@
+use crate::filter::Filter;
+use crate::resource::{Resource, ResourceResponse, StreamResponse, TimeBounds, WatchEvent};
+use crate::retry::RequestKind;
+use crate::stream::{self, PartialResult, RecordStream};
+use crate::timestamp::Timestamp;
+use crate::{Client, CloudVisionError};
+use futures::{StreamExt, TryStreamExt};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::marker::PhantomData;
+use std::time::Duration;
+use url::Url;
+
+/// Reads a single resource by its key, returned by calls such as `Client::get_device`. Nothing is
+/// sent until `send` is called.
+#[derive(Debug)]
+#[must_use = "requests do nothing until sent"]
+pub struct Get<T> {
+    client: Client,
+    key: Vec<(&'static str, String)>,
+    time: Option<Timestamp>,
+    fields: Vec<String>,
+    resource: PhantomData<fn() -> T>,
+}
+
+impl<T: Resource> Get<T> {
+    pub(crate) fn new(client: &Client, key: Vec<(&'static str, String)>) -> Self {
+        Self {
+            client: client.clone(),
+            key,
+            time: None,
+            fields: Vec::new(),
+            resource: PhantomData,
+        }
+    }
+
+    /// Reads the resource as it was at the given time instead of its current state
+    pub fn at(mut self, time: Timestamp) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Only fills in the listed fields, given by their JSON names such as `modelName`
+    pub fn fields(mut self, fields: &[&str]) -> Self {
+        self.fields = fields.iter().map(|field| field.to_string()).collect();
+        self
+    }
+
+    /// Fails the call if it does not finish within `timeout`, see `Client::with_timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.with_timeout(timeout);
+        self
+    }
+
+    pub async fn send(self) -> Result<ResourceResponse<T>, CloudVisionError> {
+        let url = self.url();
+        self.client
+            .send_json(Method::GET, url, None, RequestKind::Read)
+            .await
+    }
+
+    fn url(&self) -> Url {
+        let time = self.time.map(|time| time.to_string());
+        let field_mask = field_mask(&self.fields);
+        let mut query: Vec<(&str, &str)> = self
+            .key
+            .iter()
+            .map(|(name, value)| (*name, value.as_str()))
+            .collect();
+        if let Some(time) = &time {
+            query.push(("time", time));
+        }
+        if let Some(field_mask) = &field_mask {
+            query.push(("fieldMask", field_mask));
+        }
+        self.client.query_url(T::GET_URL, &query)
+    }
+}
+
+/// Reads every resource matching a filter, returned by calls such as `Client::get_devices`.
+/// Nothing is sent until the request is finished with `send`, `send_lenient`, `stream`, `pages`
+/// or `subscribe`.
+#[derive(Debug)]
+#[must_use = "requests do nothing until sent"]
+pub struct GetAll<T: Resource> {
+    client: Client,
+    filter: Filter<T::Match>,
+    time: Option<TimeBounds>,
+    fields: Vec<String>,
+    limit: Option<usize>,
+}
+
+/// Body of a GetAll or Subscribe request
+#[derive(Serialize)]
+struct GetAllBody<'a, M> {
+    #[serde(flatten)]
+    filter: &'a Filter<M>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    time: Option<TimeBounds>,
+    #[serde(rename = "fieldMask", skip_serializing_if = "Option::is_none")]
+    field_mask: Option<String>,
+}
+
+impl<T: Resource> GetAll<T> {
+    pub(crate) fn new(client: &Client) -> Self {
+        Self {
+            client: client.clone(),
+            filter: Filter::all(),
+            time: None,
+            fields: Vec::new(),
+            limit: None,
+        }
+    }
+
+    /// Only returns resources matching the filter, see `Filter::builder`. This replaces any
+    /// filter set before. A time set with `at` or `between` is kept unless the filter carries
+    /// its own.
+    pub fn filters(mut self, mut filter: Filter<T::Match>) -> Self {
+        if let Some(time) = filter.take_time() {
+            self.time = Some(time);
+        }
+        self.filter = filter;
+        self
+    }
+
+    /// Reads the state as it was at the given time instead of the current state
+    pub fn at(mut self, time: Timestamp) -> Self {
+        self.time = Some(TimeBounds::at(time));
+        self
+    }
+
+    /// Reads every change between start and end instead of the current state
+    pub fn between(mut self, start: Timestamp, end: Timestamp) -> Self {
+        self.time = Some(TimeBounds::between(start, end));
+        self
+    }
+
+    /// Only fills in the listed fields, given by their JSON names such as `modelName`
+    pub fn fields(mut self, fields: &[&str]) -> Self {
+        self.fields = fields.iter().map(|field| field.to_string()).collect();
+        self
+    }
+
+    /// Fails the call if it does not finish within `timeout`, see `Client::with_timeout`.
+    /// Streams only have to start within it.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.with_timeout(timeout);
+        self
+    }
+
+    /// Stops after `limit` resources. The resource APIs have no server side paging, so the
+    /// response is read as it arrives and the connection is closed once enough were read.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Reads every matching resource. Fails on the first record that can not be decoded.
+    pub async fn send(self) -> Result<Vec<ResourceResponse<T>>, CloudVisionError> {
+        if self.limit.is_some() {
+            return self.stream().await?.try_collect().await;
+        }
+        let body = self.body(self.time)?;
+        let url = self.client.build_url(T::ALL_URL);
+        self.client
+            .fetch_all::<StreamResponse<T>>(Method::POST, url, Some(body))
+            .await
+    }
+
+    /// Like `send`, but returns the resources that decoded cleanly along with every record that
+    /// did not
+    pub async fn send_lenient(
+        mut self,
+    ) -> Result<PartialResult<ResourceResponse<T>>, CloudVisionError> {
+        if let Some(limit) = self.limit.take() {
+            return stream::collect_lenient(self.stream().await?, limit).await;
+        }
+        let body = self.body(self.time)?;
+        let url = self.client.build_url(T::ALL_URL);
+        self.client
+            .fetch_all_lenient::<StreamResponse<T>>(url, body)
+            .await
+    }
+
+    /// Yields each resource as soon as it arrives rather than waiting for the whole response
+    pub async fn stream(self) -> Result<RecordStream<ResourceResponse<T>>, CloudVisionError> {
+        let body = self.body(self.time)?;
+        let records = self
+            .client
+            .open_stream::<StreamResponse<T>>(T::ALL_URL, body)
+            .await?;
+        Ok(match self.limit {
+            Some(limit) => Box::pin(records.take(limit)),
+            None => records,
+        })
+    }
+
+    /// Yields the resources in pages of `page_size` as they arrive, the last page may be shorter
+    pub async fn pages(
+        self,
+        page_size: usize,
+    ) -> Result<RecordStream<Vec<ResourceResponse<T>>>, CloudVisionError> {
+        let records = self.stream().await?;
+        Ok(Box::pin(
+            records.try_chunks(page_size.max(1)).map_err(|err| err.1),
+        ))
+    }
+
+    /// Watches the matching resources. The stream starts with an `Initial` event for every
+    /// matching resource followed by `InitialSyncComplete`, then yields `Updated` and `Deleted`
+    /// events as they happen. Subscriptions always follow the live state, so setting a time with
+    /// `at` or `between`, or a `limit`, fails with `InvalidRequest`. Dropped connections are
+    /// re-established with backoff, see `stream::subscribe`.
+    pub fn subscribe(
+        self,
+    ) -> Result<RecordStream<WatchEvent<ResourceResponse<T>>>, CloudVisionError> {
+        if self.time.is_some() || self.limit.is_some() {
+            return Err(CloudVisionError::InvalidRequest(
+                "subscriptions follow the live state and take no time or limit".to_owned(),
+            ));
+        }
+        let body = self.body(None)?;
+        Ok(stream::subscribe::<StreamResponse<T>>(
+            self.client,
+            T::SUBSCRIBE_URL,
+            body,
+        ))
+    }
+
+    fn body(&self, time: Option<TimeBounds>) -> Result<String, CloudVisionError> {
+        let body = GetAllBody {
+            filter: &self.filter,
+            time,
+            field_mask: field_mask(&self.fields),
+        };
+        Ok(serde_json::to_string(&body)?)
+    }
+}
+
+/// Sends a request body to an endpoint that changes state, returned by calls such as
+/// `Client::create_tag`. Nothing is sent until `send` is called.
+#[derive(Debug)]
+#[must_use = "requests do nothing until sent"]
+pub struct Call<B, R> {
+    client: Client,
+    method: Method,
+    path: &'static str,
+    body: B,
+    response: PhantomData<fn() -> R>,
+}
+
+impl<B, R> Call<B, R> {
+    pub(crate) fn new(client: &Client, method: Method, path: &'static str, body: B) -> Self {
+        Self {
+            client: client.clone(),
+            method,
+            path,
+            body,
+            response: PhantomData,
+        }
+    }
+
+    /// The body the setters of each endpoint fill in
+    pub(crate) fn body_mut(&mut self) -> &mut B {
+        &mut self.body
+    }
+
+    /// Fails the call if it does not finish within `timeout`, see `Client::with_timeout`
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.client = self.client.with_timeout(timeout);
+        self
+    }
+}
+
+impl<B: Serialize, R: DeserializeOwned> Call<B, R> {
+    /// Sends the request. It is only retried if the retry policy allows writes.
+    pub async fn send(self) -> Result<R, CloudVisionError> {
+        let body = serde_json::to_string(&self.body)?;
+        let url = self.client.build_url(self.path);
+        self.client
+            .send_json(self.method, url, Some(body), RequestKind::Write)
+            .await
+    }
+}
+
+/// A field mask in its JSON form, the field names joined by commas
+fn field_mask(fields: &[String]) -> Option<String> {
+    if fields.is_empty() {
+        None
+    } else {
+        Some(fields.join(","))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::{Device, StreamingStatus};
+    use crate::testing::{Reply, TestServer};
+    use crate::Config;
+
+    fn client() -> Client {
+        Client::new(Config::new(
+            "www.cv-staging.corp.arista.io".to_string(),
+            Some(443),
+            "token".to_string(),
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_get_url() {
+        let time = "2022-02-10T21:43:03.5Z".parse().unwrap();
+        let url = client()
+            .get_device("SSJ17200818")
+            .at(time)
+            .fields(&["modelName", "hostname"])
+            .url();
+        assert_eq!(url.path(), "/api/resources/inventory/v1/Device");
+        assert_eq!(
+            url.query(),
+            Some("key.deviceId=SSJ17200818&time=2022-02-10T21%3A43%3A03.500Z&fieldMask=modelName%2Chostname")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_masked_get() {
+        let server = TestServer::start(vec![Reply::new(
+            200,
+            r#"{"value":{"key":{"deviceId":"SSJ17200818"},"hostname":"leaf1"},"time":"2022-02-10T21:43:03Z"}"#,
+        )])
+        .await;
+        let device = server
+            .client()
+            .get_device("SSJ17200818")
+            .fields(&["hostname"])
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(device.value().hostname(), "leaf1");
+        assert_eq!(device.value().software_version(), "");
+        assert_eq!(
+            server.received()[0].line(),
+            "GET /api/resources/inventory/v1/Device?key.deviceId=SSJ17200818&fieldMask=hostname"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lenient_limit() {
+        let device = r#"{"result":{"value":{"key":{"deviceId":"SSJ17200818"}},"time":"2022-02-10T21:43:03Z","type":"INITIAL"}}"#;
+        let body = format!("{}\nnot json\n{}\nnot json\n", device, device);
+        let server = TestServer::start(vec![Reply::new(200, &body), Reply::new(200, &body)]).await;
+        // Reading stops at the limit, so the record after it is never seen
+        let result = server
+            .client()
+            .get_devices()
+            .limit(2)
+            .send_lenient()
+            .await
+            .unwrap();
+        assert_eq!(result.items().len(), 2);
+        assert_eq!(result.failures().len(), 1);
+        let result = server.client().get_devices().send_lenient().await.unwrap();
+        assert_eq!(result.items().len(), 2);
+        assert_eq!(result.failures().len(), 2);
+    }
+
+    #[test]
+    fn test_get_all_body() {
+        let time = "2022-02-10T21:43:03Z".parse().unwrap();
+        let request = client()
+            .get_devices()
+            .filters(
+                Filter::builder()
+                    .streaming_status(StreamingStatus::Active)
+                    .build(),
+            )
+            .at(time)
+            .fields(&["hostname"]);
+        assert_eq!(
+            request.body(request.time).unwrap(),
+            r#"{"partial_eq_filter":[{"streamingStatus":"STREAMING_STATUS_ACTIVE"}],"time":{"end":"2022-02-10T21:43:03Z"},"fieldMask":"hostname"}"#
+        );
+        // Subscriptions send no time, and refuse one
+        assert_eq!(
+            request.body(None).unwrap(),
+            r#"{"partial_eq_filter":[{"streamingStatus":"STREAMING_STATUS_ACTIVE"}],"fieldMask":"hostname"}"#
+        );
+        assert!(matches!(
+            request.subscribe(),
+            Err(CloudVisionError::InvalidRequest(_))
+        ));
+        assert!(matches!(
+            client().get_devices().limit(1).subscribe(),
+            Err(CloudVisionError::InvalidRequest(_))
+        ));
+        let request: GetAll<Device> = client().get_devices();
+        assert_eq!(
+            request.body(request.time).unwrap(),
+            r#"{"partial_eq_filter":[]}"#
+        );
+
+        // The time survives a filter set afterwards, and a filter's own time is kept
+        let filter = Filter::builder().hostname("leaf1").build();
+        let request = client().get_devices().at(time).filters(filter);
+        assert_eq!(request.time, Some(TimeBounds::at(time)));
+        let filter = Filter::builder().hostname("leaf1").at(time).build();
+        let request = client().get_devices().filters(filter);
+        assert_eq!(request.time, Some(TimeBounds::at(time)));
+        assert_eq!(
+            request.body(request.time).unwrap(),
+            r#"{"partial_eq_filter":[{"hostname":"leaf1"}],"time":{"end":"2022-02-10T21:43:03Z"}}"#
+        );
+    }
+}
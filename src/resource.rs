@@ -0,0 +1,190 @@
+use crate::stream::{Notification, ServiceResponse};
+use crate::timestamp::Timestamp;
+use crate::ErrorBody;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Limits a GetAll request to a point or a window in the past. With only an end the state as it
+/// was at that time is returned, with both a start and an end every change in between is
+/// returned.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeBounds {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    start: Option<Timestamp>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end: Option<Timestamp>,
+}
+
+impl TimeBounds {
+    /// The state as it was at the given time
+    pub fn at(time: Timestamp) -> Self {
+        TimeBounds {
+            start: None,
+            end: Some(time),
+        }
+    }
+    /// Every change between start and end
+    pub fn between(start: Timestamp, end: Timestamp) -> Self {
+        TimeBounds {
+            start: Some(start),
+            end: Some(end),
+        }
+    }
+    /// Start of the window, unset when reading the state at a single point in time
+    pub fn start(&self) -> Option<Timestamp> {
+        self.start
+    }
+    /// End of the window, or the point in time to read the state at
+    pub fn end(&self) -> Option<Timestamp> {
+        self.end
+    }
+}
+
+/// The kind of change a stream record describes
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum OperationType {
+    /// The record is part of the current state sent when a subscription starts
+    Initial,
+    /// Every `Initial` record has been sent
+    InitialSyncComplete,
+    /// The resource was created or changed
+    Updated,
+    /// The resource was removed
+    Deleted,
+    /// An operation type this version of the crate does not know about
+    #[serde(other)]
+    Unspecified,
+}
+
+/// A resource API model, tied to its endpoints and to the filter entries that match it. A new
+/// resource API only needs its model and this impl to be read with `request::Get` and
+/// `request::GetAll`. Models decode with serde defaults, so fields left out of a response, such
+/// as by a field mask, read as empty.
+pub trait Resource: DeserializeOwned + fmt::Debug + Send + 'static {
+    /// The filter entry type, see `filter::FilterBuilder`
+    type Match: Serialize + fmt::Debug;
+
+    /// Path of the `Get` endpoint, which reads a single resource by its key
+    const GET_URL: &'static str;
+    /// Path of the `GetAll` endpoint
+    const ALL_URL: &'static str;
+    /// Path of the `Subscribe` endpoint
+    const SUBSCRIBE_URL: &'static str;
+}
+
+/// A resource along with the time it last changed. This is what a `Get`, `Set` or `Delete`
+/// returns and what every record of a `GetAll` or `Subscribe` stream carries, streams also send
+/// the operation type.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ResourceResponse<T> {
+    value: T,
+    time: Timestamp,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    operation_type: Option<OperationType>,
+}
+
+impl<T> ResourceResponse<T> {
+    /// The resource
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+    /// Takes the resource, dropping the time and operation type
+    pub fn into_value(self) -> T {
+        self.value
+    }
+    /// Time the resource last changed
+    pub fn time(&self) -> Timestamp {
+        self.time
+    }
+    /// Set when the record came from a stream
+    pub fn operation_type(&self) -> Option<OperationType> {
+        self.operation_type
+    }
+}
+
+impl<T> Notification for ResourceResponse<T> {
+    fn time(&self) -> Timestamp {
+        self.time
+    }
+    fn operation_type(&self) -> Option<OperationType> {
+        self.operation_type
+    }
+}
+
+/// One line of a `GetAll` or `Subscribe` stream, a record or the error the server sent in its
+/// place
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamResponse<T> {
+    Result(Box<ResourceResponse<T>>),
+    Error(ErrorBody),
+}
+
+impl<T: DeserializeOwned> ServiceResponse for StreamResponse<T> {
+    type Record = ResourceResponse<T>;
+
+    fn into_result(self) -> Result<ResourceResponse<T>, ErrorBody> {
+        match self {
+            StreamResponse::Result(record) => Ok(*record),
+            StreamResponse::Error(err) => Err(err),
+        }
+    }
+}
+
+/// A change notification delivered by a subscription
+#[derive(Debug)]
+pub enum WatchEvent<T> {
+    /// A resource that existed when the subscription started, or was replayed after a reconnect
+    Initial(T),
+    /// The initial state has been sent in full, everything after this is a live change. This is
+    /// sent again after every reconnect once the replayed state has been delivered.
+    InitialSyncComplete,
+    /// A resource was created or changed
+    Updated(T),
+    /// A resource was removed
+    Deleted(T),
+}
+
+impl<T> WatchEvent<T> {
+    /// Wraps a record according to its operation type. Records without a known operation type are
+    /// treated as updates.
+    pub(crate) fn from_record(operation_type: Option<OperationType>, record: T) -> Self {
+        match operation_type {
+            Some(OperationType::Initial) => WatchEvent::Initial(record),
+            Some(OperationType::Deleted) => WatchEvent::Deleted(record),
+            _ => WatchEvent::Updated(record),
+        }
+    }
+
+    /// The record carried by the event, `None` for `InitialSyncComplete`
+    pub fn record(&self) -> Option<&T> {
+        match self {
+            WatchEvent::Initial(record)
+            | WatchEvent::Updated(record)
+            | WatchEvent::Deleted(record) => Some(record),
+            WatchEvent::InitialSyncComplete => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tag::TagConfig;
+
+    #[test]
+    fn test_resource_response() {
+        // A Get or Set carries no operation type
+        let body = r#"{"value":{"key":{"label":"site","value":"dc1"},"remove":false},"time":"2022-02-10T21:45:00Z"}"#;
+        let response: ResourceResponse<TagConfig> = serde_json::from_str(body).unwrap();
+        assert_eq!(response.value().key.label(), Some("site"));
+        assert_eq!(response.operation_type(), None);
+        assert_eq!(response.time(), "2022-02-10T21:45:00Z".parse().unwrap());
+
+        let line = r#"{"error":{"code":5,"message":"not found"}}"#;
+        let line: StreamResponse<TagConfig> = serde_json::from_str(line).unwrap();
+        assert_eq!(line.into_result().unwrap_err().code(), 5);
+    }
+}
@@ -0,0 +1,269 @@
+use crate::CloudVisionError;
+use chrono::{DateTime, Utc};
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Whether a request only reads state, and so can be repeated without side effects
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RequestKind {
+    Read,
+    Write,
+}
+
+/// Failures without an HTTP status that may be retried
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RetryOn {
+    /// The connection could not be established
+    Connect,
+    /// The request timed out
+    Timeout,
+    /// The connection failed while sending the request or reading the response, such as a reset
+    Request,
+}
+
+/// How failed requests are retried. Read from the `[retry]` table of a config file, unset values
+/// keep their defaults: 3 attempts, delays doubling from 200ms up to 10s with full jitter, and
+/// retries on 429, 502, 503 and 504 responses and on connection failures. Only reads are retried
+/// unless `retry_writes` is set. A `Retry-After` longer than the maximum delay ends the retries.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay_ms: u64,
+    max_delay_ms: u64,
+    jitter: bool,
+    statuses: Vec<u16>,
+    errors: Vec<RetryOn>,
+    retry_writes: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 200,
+            max_delay_ms: 10_000,
+            jitter: true,
+            statuses: vec![429, 502, 503, 504],
+            errors: vec![RetryOn::Connect, RetryOn::Timeout, RetryOn::Request],
+            retry_writes: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// A policy that makes a single attempt
+    pub fn none() -> Self {
+        Self::default().with_max_attempts(1)
+    }
+
+    /// Total number of attempts, including the first
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// The delay before the first retry, doubled for each one after it up to `max`
+    pub fn with_delays(mut self, base: Duration, max: Duration) -> Self {
+        self.base_delay_ms = base.as_millis() as u64;
+        self.max_delay_ms = max.as_millis() as u64;
+        self
+    }
+
+    /// Picks each delay at random between zero and the backoff delay, so clients that failed
+    /// together don't retry together
+    pub fn with_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// The response statuses that are retried
+    pub fn with_statuses(mut self, statuses: Vec<StatusCode>) -> Self {
+        self.statuses = statuses.iter().map(StatusCode::as_u16).collect();
+        self
+    }
+
+    /// The failures without a response that are retried
+    pub fn with_errors(mut self, errors: Vec<RetryOn>) -> Self {
+        self.errors = errors;
+        self
+    }
+
+    /// Also retries requests that change state, such as creating a tag. Only enable this when
+    /// repeating a write is harmless.
+    pub fn with_retry_writes(mut self, retry_writes: bool) -> Self {
+        self.retry_writes = retry_writes;
+        self
+    }
+
+    /// How long to wait before making attempt `attempt + 1`, or `None` if the error should be
+    /// returned. A `Retry-After` from the server is used instead of the backoff delay, unless it
+    /// is longer than the maximum delay, in which case the error is returned rather than
+    /// stalling the call.
+    pub(crate) fn retry_delay(
+        &self,
+        kind: RequestKind,
+        attempt: u32,
+        err: &CloudVisionError,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts
+            || (kind == RequestKind::Write && !self.retry_writes)
+            || !self.is_retryable(err)
+        {
+            return None;
+        }
+        match retry_after {
+            Some(delay) if delay > Duration::from_millis(self.max_delay_ms) => None,
+            Some(delay) => Some(delay),
+            None => Some(self.backoff(attempt)),
+        }
+    }
+
+    fn is_retryable(&self, err: &CloudVisionError) -> bool {
+        if let CloudVisionError::Timeout { .. } = err {
+            return self.errors.contains(&RetryOn::Timeout);
+        }
+        if let CloudVisionError::Request(err) = err {
+            let kind = if err.is_connect() {
+                RetryOn::Connect
+            } else if err.is_request() || err.is_body() {
+                RetryOn::Request
+            } else {
+                return false;
+            };
+            return self.errors.contains(&kind);
+        }
+        err.api_error()
+            .is_some_and(|api| self.statuses.contains(&api.status().as_u16()))
+    }
+
+    /// Exponential backoff delay after `attempt` failed attempts
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay_ms
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay_ms);
+        let delay = if self.jitter {
+            fastrand::u64(0..=delay)
+        } else {
+            delay
+        };
+        Duration::from_millis(delay)
+    }
+}
+
+/// Reads a `Retry-After` header, given either in seconds or as an HTTP date
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+    if let Ok(secs) = value.parse::<u64>() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_retry_delay() {
+        let policy = RetryPolicy::default()
+            .with_jitter(false)
+            .with_delays(Duration::from_millis(100), Duration::from_millis(300));
+        let unavailable =
+            CloudVisionError::from_status(StatusCode::SERVICE_UNAVAILABLE, "/api/x", "");
+        let delay = |attempt| policy.retry_delay(RequestKind::Read, attempt, &unavailable, None);
+        assert_eq!(delay(1), Some(Duration::from_millis(100)));
+        assert_eq!(delay(2), Some(Duration::from_millis(200)));
+        assert_eq!(delay(3), None);
+        let policy = policy.with_max_attempts(5);
+        assert_eq!(
+            policy.retry_delay(RequestKind::Read, 4, &unavailable, None),
+            Some(Duration::from_millis(300))
+        );
+
+        // Writes and non-retryable errors are returned straight away
+        assert_eq!(
+            policy.retry_delay(RequestKind::Write, 1, &unavailable, None),
+            None
+        );
+        let not_found = CloudVisionError::from_status(StatusCode::NOT_FOUND, "/api/x", "");
+        assert_eq!(
+            policy.retry_delay(RequestKind::Read, 1, &not_found, None),
+            None
+        );
+        let policy = policy.with_retry_writes(true);
+        assert!(policy
+            .retry_delay(RequestKind::Write, 1, &unavailable, None)
+            .is_some());
+
+        let timeout = CloudVisionError::Timeout { url: None };
+        assert!(policy
+            .retry_delay(RequestKind::Read, 1, &timeout, None)
+            .is_some());
+        let policy = policy.with_errors(vec![RetryOn::Connect]);
+        assert_eq!(
+            policy.retry_delay(RequestKind::Read, 1, &timeout, None),
+            None
+        );
+
+        let throttled = CloudVisionError::from_status(StatusCode::TOO_MANY_REQUESTS, "/api/x", "");
+        assert_eq!(
+            policy.retry_delay(
+                RequestKind::Read,
+                1,
+                &throttled,
+                Some(Duration::from_millis(250))
+            ),
+            Some(Duration::from_millis(250))
+        );
+        // Waiting longer than the maximum delay is left to the caller
+        assert_eq!(
+            policy.retry_delay(
+                RequestKind::Read,
+                1,
+                &throttled,
+                Some(Duration::from_secs(3600))
+            ),
+            None
+        );
+        assert_eq!(
+            RetryPolicy::none().retry_delay(RequestKind::Read, 1, &throttled, None),
+            None
+        );
+    }
+
+    #[test]
+    fn test_backoff_jitter() {
+        let policy = RetryPolicy::default();
+        for attempt in 1..10 {
+            assert!(policy.backoff(attempt) <= Duration::from_secs(10));
+        }
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("120"));
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(120)));
+        headers.insert(
+            RETRY_AFTER,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        assert_eq!(retry_after(&headers), Some(Duration::ZERO));
+        headers.insert(RETRY_AFTER, HeaderValue::from_static("soon"));
+        assert_eq!(retry_after(&headers), None);
+    }
+}
@@ -0,0 +1,632 @@
+use crate::resource::{OperationType, WatchEvent};
+use crate::timestamp::Timestamp;
+use crate::{Client, CloudVisionError, ErrorBody};
+use futures::stream::{self, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use std::fmt;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+use tracing::Span;
+use url::Url;
+
+/// Delay before the first attempt to re-establish a dropped subscription
+const RECONNECT_BASE_DELAY: Duration = Duration::from_millis(500);
+/// Upper bound for the delay between attempts to re-establish a subscription
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Typed records yielded one at a time as a resource API response arrives
+pub type RecordStream<T> = Pin<Box<dyn Stream<Item = Result<T, CloudVisionError>> + Send>>;
+
+/// Implemented by the `{"result": ...} | {"error": ...}` envelopes sent by the resource APIs
+pub trait ServiceResponse: DeserializeOwned {
+    type Record;
+
+    /// Returns the record, or the error the server sent in its place
+    fn into_result(self) -> Result<Self::Record, ErrorBody>;
+}
+
+/// Implemented by stream records that carry a notification time and operation type
+pub trait Notification {
+    /// Time of the change the record describes
+    fn time(&self) -> Timestamp;
+    /// Set when the record came from a subscription
+    fn operation_type(&self) -> Option<OperationType>;
+}
+
+/// A record from a resource API stream that could not be turned into a typed value
+#[derive(Debug)]
+pub struct RecordError {
+    raw: String,
+    kind: RecordErrorKind,
+}
+
+/// Why a record could not be used
+#[derive(Debug)]
+pub enum RecordErrorKind {
+    /// The record is not valid JSON or does not match the expected model
+    Decode(serde_json::Error),
+    /// The server sent an in-band error in place of a record
+    Server(ErrorBody),
+}
+
+impl RecordError {
+    /// The raw JSON of the record as it was received
+    pub fn raw(&self) -> &str {
+        &self.raw
+    }
+    /// Why the record could not be used
+    pub fn kind(&self) -> &RecordErrorKind {
+        &self.kind
+    }
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            RecordErrorKind::Decode(err) => write!(f, "could not decode record: {}", err),
+            RecordErrorKind::Server(body) => {
+                write!(f, "server sent error {}: {}", body.code(), body.message())
+            }
+        }
+    }
+}
+
+/// The records that decoded cleanly along with every record that did not. A result with no
+/// failures is complete.
+#[derive(Debug)]
+pub struct PartialResult<T> {
+    items: Vec<T>,
+    failures: Vec<RecordError>,
+}
+
+impl<T> PartialResult<T> {
+    /// Records that decoded cleanly, in the order they were received
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+    /// Records that could not be used, in the order they were received
+    pub fn failures(&self) -> &[RecordError] {
+        &self.failures
+    }
+    /// True when every record in the response decoded cleanly
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+    /// Splits the result into the decoded records and the failures
+    pub fn into_parts(self) -> (Vec<T>, Vec<RecordError>) {
+        (self.items, self.failures)
+    }
+}
+
+/// Decodes a single newline delimited record
+pub(crate) fn decode_record<R: ServiceResponse>(raw: &[u8]) -> Result<R::Record, RecordError> {
+    let kind = match serde_json::from_slice::<R>(raw) {
+        Ok(response) => match response.into_result() {
+            Ok(record) => return Ok(record),
+            Err(body) => RecordErrorKind::Server(body),
+        },
+        Err(err) => RecordErrorKind::Decode(err),
+    };
+    Err(RecordError {
+        raw: String::from_utf8_lossy(raw).into_owned(),
+        kind,
+    })
+}
+
+/// Strips surrounding whitespace, returning `None` for blank lines
+fn trim_record(line: &[u8]) -> Option<&[u8]> {
+    let start = line.iter().position(|b| !b.is_ascii_whitespace())?;
+    let end = line.iter().rposition(|b| !b.is_ascii_whitespace())?;
+    Some(&line[start..=end])
+}
+
+fn records(body: &str) -> impl Iterator<Item = &[u8]> {
+    body.as_bytes()
+        .split(|b| *b == b'\n')
+        .filter_map(trim_record)
+}
+
+/// Decodes every record of a response, failing on the first record that can not be used
+pub(crate) fn decode_strict<R: ServiceResponse>(
+    body: &str,
+) -> Result<Vec<R::Record>, CloudVisionError> {
+    records(body)
+        .map(|raw| decode_record::<R>(raw).map_err(CloudVisionError::Record))
+        .collect()
+}
+
+/// Decodes every record of a response, collecting the records that can not be used
+pub(crate) fn decode_lenient<R: ServiceResponse>(body: &str) -> PartialResult<R::Record> {
+    let mut result = PartialResult {
+        items: Vec::new(),
+        failures: Vec::new(),
+    };
+    for raw in records(body) {
+        match decode_record::<R>(raw) {
+            Ok(record) => result.items.push(record),
+            Err(err) => result.failures.push(err),
+        }
+    }
+    result
+}
+
+/// Reads records until `limit` decoded cleanly, keeping the records that did not. The rest of
+/// the stream is never read, dropping it closes the connection.
+pub(crate) async fn collect_lenient<T>(
+    mut records: RecordStream<T>,
+    limit: usize,
+) -> Result<PartialResult<T>, CloudVisionError> {
+    let mut result = PartialResult {
+        items: Vec::new(),
+        failures: Vec::new(),
+    };
+    while result.items.len() < limit {
+        match records.next().await {
+            Some(Ok(record)) => result.items.push(record),
+            Some(Err(CloudVisionError::Record(err))) => result.failures.push(err),
+            Some(Err(err)) => return Err(err),
+            None => break,
+        }
+    }
+    Ok(result)
+}
+
+/// State carried between polls of a record stream
+struct Lines<S> {
+    chunks: S,
+    buf: Vec<u8>,
+    done: bool,
+}
+
+/// Turns a stream of response body chunks into a stream of decoded records. A record is yielded
+/// as soon as its terminating newline arrives, and the next chunk is only read once the consumer
+/// asks for another record, so a slow consumer slows the download instead of growing a buffer.
+/// Records that can not be decoded are yielded as `CloudVisionError::Record` and the stream
+/// carries on, a transport error ends the stream.
+pub(crate) fn decode_stream<R, S, B, E>(chunks: S) -> RecordStream<R::Record>
+where
+    R: ServiceResponse,
+    R::Record: Send + 'static,
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: AsRef<[u8]>,
+    E: Into<CloudVisionError>,
+{
+    let state = Lines {
+        chunks: Box::pin(chunks),
+        buf: Vec::new(),
+        done: false,
+    };
+    let records = stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(newline) = state.buf.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = state.buf.drain(..=newline).collect();
+                if let Some(raw) = trim_record(&line) {
+                    let record = decode_record::<R>(raw).map_err(CloudVisionError::Record);
+                    return Some((record, state));
+                }
+                continue;
+            }
+            if state.done {
+                let rest = std::mem::take(&mut state.buf);
+                let raw = trim_record(&rest)?;
+                let record = decode_record::<R>(raw).map_err(CloudVisionError::Record);
+                return Some((record, state));
+            }
+            match state.chunks.next().await {
+                Some(Ok(chunk)) => state.buf.extend_from_slice(chunk.as_ref()),
+                Some(Err(err)) => {
+                    state.buf.clear();
+                    state.done = true;
+                    return Some((Err(err.into()), state));
+                }
+                None => state.done = true,
+            }
+        }
+    });
+    Box::pin(records)
+}
+
+/// State carried between polls of a subscription
+struct Watch<T> {
+    client: Client,
+    path: &'static str,
+    body: String,
+    records: Option<RecordStream<T>>,
+    /// The newest notification delivered by a completed initial sync or after it
+    last_seen: Option<Timestamp>,
+    /// The newest `INITIAL` notification of the current connection, until its sync completes
+    syncing: Option<Timestamp>,
+    delay: Duration,
+    done: bool,
+}
+
+impl<T: Notification> Watch<T> {
+    /// After a reconnect the server replays the current state as `INITIAL` notifications. Those
+    /// that are not newer than the last delivered notification were already seen. Only a
+    /// completed initial sync counts, as a connection that dropped part way through its initial
+    /// state may not have delivered the older notifications.
+    fn is_replay(&self, record: &T) -> bool {
+        if record.operation_type() != Some(OperationType::Initial) {
+            return false;
+        }
+        self.last_seen
+            .is_some_and(|last_seen| record.time() <= last_seen)
+    }
+
+    fn record_seen(&mut self, record: &T) {
+        let seen = if record.operation_type() == Some(OperationType::Initial) {
+            &mut self.syncing
+        } else {
+            &mut self.last_seen
+        };
+        let time = record.time();
+        if seen.is_none_or(|seen| time > seen) {
+            *seen = Some(time);
+        }
+    }
+
+    fn sync_complete(&mut self) {
+        if let Some(time) = self.syncing.take() {
+            if self.last_seen.is_none_or(|last_seen| time > last_seen) {
+                self.last_seen = Some(time);
+            }
+        }
+    }
+
+    fn disconnected(&mut self) {
+        self.records = None;
+        self.syncing = None;
+    }
+}
+
+/// The end of the initial state is marked by a record that has a type but no value, so it does
+/// not decode as a typed record
+#[derive(Deserialize)]
+struct SyncMarker {
+    result: SyncMarkerResult,
+}
+
+#[derive(Deserialize)]
+struct SyncMarkerResult {
+    #[serde(rename = "type")]
+    operation_type: OperationType,
+}
+
+fn is_sync_complete(raw: &str) -> bool {
+    serde_json::from_str::<SyncMarker>(raw)
+        .map(|marker| marker.result.operation_type == OperationType::InitialSyncComplete)
+        .unwrap_or(false)
+}
+
+/// Counts the records of a stream into its request span, and records how long the stream ran
+/// once it ends
+pub(crate) fn traced<T>(records: RecordStream<T>, span: Span, started: Instant) -> RecordStream<T>
+where
+    T: Send + 'static,
+{
+    let state = Some((records, span, 0_usize));
+    Box::pin(stream::unfold(state, move |state| async move {
+        let (mut records, span, count) = state?;
+        match records.next().await {
+            Some(record) => {
+                let count = count + usize::from(record.is_ok());
+                Some((record, Some((records, span, count))))
+            }
+            None => {
+                span.record("records", count);
+                span.record("latency_ms", started.elapsed().as_millis() as u64);
+                tracing::debug!(parent: &span, "stream finished");
+                None
+            }
+        }
+    }))
+}
+
+/// Adds up the size of a response body's chunks and records it into its request span once the
+/// body ends
+pub(crate) fn counted<S, B, E>(chunks: S, span: Span) -> impl Stream<Item = Result<B, E>>
+where
+    S: Stream<Item = Result<B, E>>,
+    B: AsRef<[u8]>,
+{
+    let state = Some((Box::pin(chunks), span, 0_usize));
+    stream::unfold(state, |state| async move {
+        let (mut chunks, span, bytes) = state?;
+        match chunks.next().await {
+            Some(chunk) => {
+                let bytes = bytes + chunk.as_ref().map_or(0, |chunk| chunk.as_ref().len());
+                Some((chunk, Some((chunks, span, bytes))))
+            }
+            None => {
+                span.record("bytes", bytes);
+                None
+            }
+        }
+    })
+}
+
+/// Fails a response body with `Timeout` when no chunk arrives within `limit`, and ends it after
+/// the failure
+pub(crate) fn idle_timeout<S, B, E>(
+    chunks: S,
+    limit: Option<Duration>,
+    url: Url,
+) -> impl Stream<Item = Result<B, CloudVisionError>> + Send + 'static
+where
+    S: Stream<Item = Result<B, E>> + Send + 'static,
+    B: Send + 'static,
+    E: Into<CloudVisionError>,
+{
+    stream::unfold(Some(Box::pin(chunks)), move |chunks| {
+        let url = url.clone();
+        async move {
+            let mut chunks = chunks?;
+            let next = crate::timed(limit, url, async { Ok(chunks.next().await) }).await;
+            match next {
+                Ok(next) => next.map(|chunk| (chunk.map_err(Into::into), Some(chunks))),
+                Err(err) => Some((Err(err), None)),
+            }
+        }
+    })
+}
+
+/// Opens a long lived subscription. When the connection drops, or the server answers with a
+/// transient error, the subscription is re-established with exponential backoff. Notifications
+/// that are replayed on reconnect and were already delivered are skipped, so consumers see each
+/// change once; when the connection drops during the initial state, that state is delivered
+/// again in full. Deletions that happen while disconnected can not be recovered. Errors that will
+/// not go away by retrying, such as a 401, are yielded and end the stream.
+pub(crate) fn subscribe<R>(
+    client: Client,
+    path: &'static str,
+    body: String,
+) -> RecordStream<WatchEvent<R::Record>>
+where
+    R: ServiceResponse,
+    R::Record: Notification + Send + 'static,
+{
+    let state = Watch {
+        client: client.without_read_timeout(),
+        path,
+        body,
+        records: None,
+        last_seen: None,
+        syncing: None,
+        delay: RECONNECT_BASE_DELAY,
+        done: false,
+    };
+    let notifications = stream::unfold(state, |mut state| async move {
+        loop {
+            if state.done {
+                return None;
+            }
+            let records = match state.records.as_mut() {
+                Some(records) => records,
+                None => {
+                    match state
+                        .client
+                        .open_stream::<R>(state.path, state.body.clone())
+                        .await
+                    {
+                        Ok(records) => state.records = Some(records),
+                        Err(err) if err.is_transient() => {
+                            tokio::time::sleep(state.delay).await;
+                            state.delay = (state.delay * 2).min(RECONNECT_MAX_DELAY);
+                        }
+                        Err(err) => {
+                            state.done = true;
+                            return Some((Err(err), state));
+                        }
+                    }
+                    continue;
+                }
+            };
+            match records.next().await {
+                Some(Ok(record)) => {
+                    state.delay = RECONNECT_BASE_DELAY;
+                    if state.is_replay(&record) {
+                        continue;
+                    }
+                    state.record_seen(&record);
+                    let event = WatchEvent::from_record(record.operation_type(), record);
+                    return Some((Ok(event), state));
+                }
+                Some(Err(CloudVisionError::Record(err))) if is_sync_complete(err.raw()) => {
+                    state.sync_complete();
+                    return Some((Ok(WatchEvent::InitialSyncComplete), state));
+                }
+                Some(Err(err @ CloudVisionError::Record(_))) => return Some((Err(err), state)),
+                Some(Err(_)) | None => {
+                    state.disconnected();
+                    tokio::time::sleep(state.delay).await;
+                    state.delay = (state.delay * 2).min(RECONNECT_MAX_DELAY);
+                }
+            }
+        }
+    });
+    Box::pin(notifications)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::Device;
+    use crate::resource::StreamResponse;
+    use crate::testing::{Reply, TestServer};
+
+    type DeviceServiceResponse = StreamResponse<Device>;
+
+    const DEVICE: &str = r#"{"result":{"value":{"key":{"deviceId":"SSJ17200818"},"softwareVersion":"4.27.0F","modelName":"DCS-7280SR2-48YC6","hardwareRevision":"11.01","fqdn":"leaf1.example.com","hostname":"leaf1","domainName":"example.com","systemMacAddress":"00:1c:73:00:00:01","bootTime":"2022-02-10T21:43:03Z","streamingStatus":"STREAMING_STATUS_ACTIVE"},"time":"2022-02-10T21:45:00Z","type":"INITIAL"}}"#;
+    const IN_BAND_ERROR: &str = r#"{"error":{"code":13,"message":"stream reset"}}"#;
+    const MALFORMED: &str = r#"{"result":{"value":{"key":"#;
+
+    #[test]
+    fn test_decode_strict() {
+        let body = format!("{}\n{}\n", DEVICE, DEVICE);
+        let devices = decode_strict::<DeviceServiceResponse>(&body).unwrap();
+        assert_eq!(devices.len(), 2);
+
+        let body = format!("{}\n{}\n", DEVICE, MALFORMED);
+        match decode_strict::<DeviceServiceResponse>(&body) {
+            Err(CloudVisionError::Record(err)) => assert_eq!(err.raw(), MALFORMED),
+            other => panic!("expected a record error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_decode_lenient() {
+        let body = format!("{}\n{}\n\n{}\n", MALFORMED, DEVICE, IN_BAND_ERROR);
+        let result = decode_lenient::<DeviceServiceResponse>(&body);
+        assert!(!result.is_complete());
+        assert_eq!(result.items().len(), 1);
+        let failures = result.failures();
+        assert_eq!(failures.len(), 2);
+        assert!(matches!(failures[0].kind(), RecordErrorKind::Decode(_)));
+        match failures[1].kind() {
+            RecordErrorKind::Server(body) => assert_eq!(body.code(), 13),
+            other => panic!("expected a server error, got {:?}", other),
+        }
+        assert_eq!(failures[1].raw(), IN_BAND_ERROR);
+
+        let result = decode_lenient::<DeviceServiceResponse>(DEVICE);
+        assert!(result.is_complete());
+    }
+
+    #[tokio::test]
+    async fn test_decode_stream() {
+        // Records split across chunks, a blank line, and a final record without a newline
+        let body = format!("{}\n\n{}\n{}", DEVICE, MALFORMED, DEVICE);
+        let (first, rest) = body.split_at(100);
+        let chunks: Vec<Result<Vec<u8>, CloudVisionError>> =
+            vec![Ok(first.as_bytes().to_vec()), Ok(rest.as_bytes().to_vec())];
+        let mut records = decode_stream::<DeviceServiceResponse, _, _, _>(stream::iter(chunks));
+        assert!(records.next().await.unwrap().is_ok());
+        match records.next().await.unwrap() {
+            Err(CloudVisionError::Record(err)) => assert_eq!(err.raw(), MALFORMED),
+            other => panic!("expected a record error, got {:?}", other),
+        }
+        assert!(records.next().await.unwrap().is_ok());
+        assert!(records.next().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_idle_timeout() {
+        let url = Url::parse("https://cv.example.com/api").unwrap();
+        let first: Result<Vec<u8>, CloudVisionError> = Ok(format!("{}\n", DEVICE).into_bytes());
+        // The server sends one chunk and then goes quiet
+        let chunks = stream::iter(vec![first]).chain(stream::pending());
+        let chunks = idle_timeout(chunks, Some(Duration::from_millis(10)), url);
+        let mut records = decode_stream::<DeviceServiceResponse, _, _, _>(chunks);
+        assert!(records.next().await.unwrap().is_ok());
+        assert!(matches!(
+            records.next().await.unwrap(),
+            Err(CloudVisionError::Timeout { .. })
+        ));
+    }
+
+    #[test]
+    fn test_watch_skips_replayed_notifications() {
+        let config = crate::Config::new("localhost".to_string(), None, "token".to_string());
+        let mut watch = Watch {
+            client: Client::new(config).unwrap(),
+            path: crate::device::DEVICE_SUBSCRIBE_URL,
+            body: String::new(),
+            records: None,
+            last_seen: None,
+            syncing: None,
+            delay: RECONNECT_BASE_DELAY,
+            done: false,
+        };
+        let record = |time: &str, op: &str| {
+            let line = DEVICE
+                .replace("2022-02-10T21:45:00Z", time)
+                .replace("INITIAL", op);
+            decode_record::<DeviceServiceResponse>(line.as_bytes()).unwrap()
+        };
+        let first = record("2022-02-10T21:45:00.5Z", "INITIAL");
+        assert!(!watch.is_replay(&first));
+        watch.record_seen(&first);
+        assert!(!watch.is_replay(&record("2022-02-10T21:45:00Z", "INITIAL")));
+        watch.sync_complete();
+        watch.disconnected();
+        assert!(watch.is_replay(&record("2022-02-10T21:45:00Z", "INITIAL")));
+        assert!(watch.is_replay(&record("2022-02-10T21:45:00.500Z", "INITIAL")));
+        assert!(!watch.is_replay(&record("2022-02-10T21:45:01Z", "INITIAL")));
+        assert!(!watch.is_replay(&record("2022-02-10T21:45:00Z", "UPDATED")));
+    }
+
+    #[tokio::test]
+    async fn test_watch_drop_during_initial_sync() {
+        let at = |time: &str| DEVICE.replace("2022-02-10T21:45:00Z", time);
+        let marker = r#"{"result":{"time":"2022-02-10T21:45:00Z","type":"INITIAL_SYNC_COMPLETE"}}"#;
+        let server = TestServer::start(vec![
+            Reply::new(200, &format!("{}\n", at("2022-02-10T21:45:10Z"))),
+            Reply::new(
+                200,
+                &format!(
+                    "{}\n{}\n{}\n",
+                    at("2022-02-10T21:45:05Z"),
+                    at("2022-02-10T21:45:10Z"),
+                    marker
+                ),
+            ),
+        ])
+        .await;
+        let events: Vec<_> = subscribe::<DeviceServiceResponse>(
+            server.client(),
+            crate::device::DEVICE_SUBSCRIBE_URL,
+            String::new(),
+        )
+        .take(4)
+        .map(Result::unwrap)
+        .collect()
+        .await;
+        let times: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                WatchEvent::Initial(record) => Some(record.time().to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            times,
+            [
+                "2022-02-10T21:45:10Z",
+                "2022-02-10T21:45:05Z",
+                "2022-02-10T21:45:10Z"
+            ]
+        );
+        assert!(matches!(events[3], WatchEvent::InitialSyncComplete));
+    }
+
+    #[test]
+    fn test_sync_complete_marker() {
+        let marker = r#"{"result":{"time":"2022-02-10T21:45:00Z","type":"INITIAL_SYNC_COMPLETE"}}"#;
+        let err = decode_record::<DeviceServiceResponse>(marker.as_bytes()).unwrap_err();
+        assert!(is_sync_complete(err.raw()));
+        assert!(!is_sync_complete(MALFORMED));
+        assert!(!is_sync_complete(IN_BAND_ERROR));
+
+        let record = decode_record::<DeviceServiceResponse>(DEVICE.as_bytes()).unwrap();
+        assert_eq!(record.operation_type(), Some(OperationType::Initial));
+        let event = WatchEvent::from_record(record.operation_type(), record);
+        assert!(matches!(event, WatchEvent::Initial(_)));
+    }
+
+    #[tokio::test]
+    async fn test_decode_stream_transport_error() {
+        let chunks: Vec<Result<Vec<u8>, CloudVisionError>> = vec![
+            Ok(format!("{}\n", DEVICE).into_bytes()),
+            Err(CloudVisionError::NoToken),
+            Ok(format!("{}\n", DEVICE).into_bytes()),
+        ];
+        let mut records = decode_stream::<DeviceServiceResponse, _, _, _>(stream::iter(chunks));
+        assert!(records.next().await.unwrap().is_ok());
+        assert!(matches!(
+            records.next().await.unwrap(),
+            Err(CloudVisionError::NoToken)
+        ));
+        assert!(records.next().await.is_none());
+    }
+}
@@ -1,47 +1,34 @@
+use crate::filter::FilterBuilder;
+use crate::request::Call;
+use crate::resource::Resource;
+use crate::timestamp::Timestamp;
 use serde::{Deserialize, Serialize};
 
+pub const TAG_ASSIGNMENT_CONFIG_GET_URL: &str = "/api/resources/tag/v2/TagAssignmentConfig";
 pub const TAG_ASSIGNMENT_CONFIG_URL: &str = "/api/resources/tag/v2/TagAssignmentConfig/all";
 pub const TAG_CONFIG_URL: &str = "/api/resources/tag/v2/TagConfig";
+pub const TAG_GET_URL: &str = "/api/resources/tag/v2/Tag";
 pub const TAG_URL: &str = "/api/resources/tag/v2/Tag/all";
+pub const TAG_ASSIGNMENT_CONFIG_SUBSCRIBE_URL: &str =
+    "/api/resources/tag/v2/TagAssignmentConfig/subscribe";
+pub const TAG_SUBSCRIBE_URL: &str = "/api/resources/tag/v2/Tag/subscribe";
 
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(untagged)]
-pub enum Response {
-    TasrResult(Box<TagAssignmentConfigStreamResponse>),
-    TsrResult(Box<TagStreamResponse>),
-    Error,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "lowercase")]
-pub enum TagAssignmentServiceResponse {
-    Result(Box<TagAssignmentConfigStreamResponse>),
-    Error,
-}
-
-/// TagServiceResponse handles responses from method calls against the tag service
-// TODO handle a single getOne vs tagstream
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "lowercase")]
-pub enum TagServiceResponse {
-    Result(Box<TagStreamResponse>),
-    Error,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
-pub struct TagStreamResponse {
-    value: Tag,
-    time: String,
-    #[serde(rename = "type")]
-    operation_type: Option<String>,
-}
-
-#[derive(Serialize, Deserialize, Debug)]
+/// A tag as reported by the resource API
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
 pub struct Tag {
     key: TagKey,
     creator_type: Option<CreatorType>,
 }
 
+impl Resource for Tag {
+    type Match = TagMatch;
+
+    const GET_URL: &'static str = TAG_GET_URL;
+    const ALL_URL: &'static str = TAG_URL;
+    const SUBSCRIBE_URL: &'static str = TAG_SUBSCRIBE_URL;
+}
+
 impl Tag {
     pub fn new(key: TagKey) -> Self {
         Tag {
@@ -49,10 +36,59 @@ impl Tag {
             creator_type: None,
         }
     }
+
+    pub fn key(&self) -> &TagKey {
+        &self.key
+    }
+
+    /// Whether the tag was created by a user or by CloudVision itself
+    pub fn creator_type(&self) -> Option<CreatorType> {
+        self.creator_type
+    }
+}
+
+/// A filter entry for tags, every field that is set must match. Built with the setters on
+/// `FilterBuilder<TagMatch>`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct TagMatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<TagKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    creator_type: Option<CreatorType>,
+}
+
+impl TagMatch {
+    fn key(&mut self) -> &mut TagKey {
+        self.key.get_or_insert_with(TagKey::new)
+    }
+}
+
+impl FilterBuilder<TagMatch> {
+    pub fn label(mut self, label: &str) -> Self {
+        self.entry().key().label = Some(label.to_owned());
+        self
+    }
+    pub fn value(mut self, value: &str) -> Self {
+        self.entry().key().value = Some(value.to_owned());
+        self
+    }
+    pub fn element_type(mut self, element_type: ElementType) -> Self {
+        self.entry().key().element_type = Some(element_type);
+        self
+    }
+    pub fn workspace(mut self, workspace_id: &str) -> Self {
+        self.entry().key().workspace_id = Some(workspace_id.to_owned());
+        self
+    }
+    pub fn creator_type(mut self, creator_type: CreatorType) -> Self {
+        self.entry().creator_type = Some(creator_type);
+        self
+    }
 }
 
 /// TagConfig is used to CRUD tags
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct TagConfig {
     pub key: TagKey,
     pub remove: bool,
@@ -64,27 +100,56 @@ impl TagConfig {
     }
 }
 
+impl<R> Call<TagConfig, R> {
+    pub fn workspace(mut self, workspace_id: &str) -> Self {
+        self.body_mut().key.set_workspace_id(workspace_id);
+        self
+    }
+    pub fn element_type(mut self, element_type: ElementType) -> Self {
+        self.body_mut().key.set_element_type(element_type);
+        self
+    }
+    pub fn label(mut self, label: &str, value: &str) -> Self {
+        self.body_mut().key.set_label(label, value);
+        self
+    }
+    /// Removes the tag instead of creating it
+    pub fn remove(mut self, remove: bool) -> Self {
+        self.body_mut().remove = remove;
+        self
+    }
+}
+
 // TODO work through tag config options
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TagConfigRequest {
     pub key: TagKey,
-    pub time: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time: Option<Timestamp>,
 }
 
-/// TagConfigResponse handles all the single action TagConfig responses: TagConfig, TagConfigSet,
-/// and TagConfigDelete
-#[derive(Serialize, Deserialize, Debug)]
-pub struct TagConfigResponse {
-    pub value: TagConfig,
-    pub time: String,
+impl TagConfigRequest {
+    pub fn new(key: TagKey) -> Self {
+        TagConfigRequest { key, time: None }
+    }
+
+    /// Reads the config as it was at the given time instead of its current state
+    pub fn at(mut self, time: Timestamp) -> Self {
+        self.time = Some(time);
+        self
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct TagKey {
+    #[serde(skip_serializing_if = "Option::is_none")]
     workspace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     element_type: Option<ElementType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     value: Option<String>,
 }
 
@@ -97,9 +162,27 @@ impl TagKey {
             value: None,
         }
     }
+    /// Starts building a key, for example
+    /// `TagKey::builder().element_type(ElementType::Device).label("site", "dc1").build()`
+    pub fn builder() -> TagKeyBuilder {
+        TagKeyBuilder::default()
+    }
+    pub fn workspace_id(&self) -> Option<&str> {
+        self.workspace_id.as_deref()
+    }
+    pub fn element_type(&self) -> Option<ElementType> {
+        self.element_type
+    }
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+    pub fn value(&self) -> Option<&str> {
+        self.value.as_deref()
+    }
     pub fn set_workspace_id(&mut self, workspace_id: &str) {
         self.workspace_id = Some(workspace_id.to_owned());
     }
+    #[deprecated(note = "use `label`")]
     pub fn get_label(&self) -> Option<&String> {
         self.label.as_ref()
     }
@@ -110,26 +193,64 @@ impl TagKey {
     pub fn set_element_type(&mut self, et: ElementType) {
         self.element_type = Some(et);
     }
+    #[deprecated(note = "use `workspace_id`")]
     pub fn get_workspace_id(&self) -> Option<&String> {
         self.workspace_id.as_ref()
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
-pub struct TagAssignmentConfigStreamResponse {
-    value: TagAssignmentConfig,
-    time: String,
-    #[serde(rename = "type")]
-    operation_type: Option<String>,
+/// Builds a `TagKey`, see `TagKey::builder`
+#[derive(Debug, Default)]
+pub struct TagKeyBuilder {
+    key: TagKey,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl TagKeyBuilder {
+    pub fn workspace(mut self, workspace_id: &str) -> Self {
+        self.key.set_workspace_id(workspace_id);
+        self
+    }
+    pub fn element_type(mut self, element_type: ElementType) -> Self {
+        self.key.set_element_type(element_type);
+        self
+    }
+    pub fn label(mut self, label: &str, value: &str) -> Self {
+        self.key.set_label(label, value);
+        self
+    }
+    pub fn build(self) -> TagKey {
+        self.key
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(default)]
 pub struct TagAssignmentConfig {
     key: TagAssignmentKey,
     remove: Option<bool>,
 }
-#[derive(Serialize, Deserialize, Debug)]
-#[serde(rename_all = "camelCase")]
+
+impl Resource for TagAssignmentConfig {
+    type Match = TagAssignmentMatch;
+
+    const GET_URL: &'static str = TAG_ASSIGNMENT_CONFIG_GET_URL;
+    const ALL_URL: &'static str = TAG_ASSIGNMENT_CONFIG_URL;
+    const SUBSCRIBE_URL: &'static str = TAG_ASSIGNMENT_CONFIG_SUBSCRIBE_URL;
+}
+
+impl TagAssignmentConfig {
+    pub fn key(&self) -> &TagAssignmentKey {
+        &self.key
+    }
+
+    /// Whether the assignment is being removed, unset means it is not
+    pub fn remove(&self) -> bool {
+        self.remove.unwrap_or(false)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Default)]
+#[serde(rename_all = "camelCase", default)]
 pub struct TagAssignmentKey {
     workspace_id: String,
     element_type: ElementType,
@@ -139,8 +260,90 @@ pub struct TagAssignmentKey {
     interface_id: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl TagAssignmentKey {
+    pub fn workspace_id(&self) -> &str {
+        &self.workspace_id
+    }
+    pub fn element_type(&self) -> ElementType {
+        self.element_type
+    }
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+    pub fn device_id(&self) -> &str {
+        &self.device_id
+    }
+    /// The interface the tag is assigned to, empty for device tags
+    pub fn interface_id(&self) -> &str {
+        &self.interface_id
+    }
+}
+
+/// A filter entry for tag assignments, every field that is set must match. Built with the setters
+/// on `FilterBuilder<TagAssignmentMatch>`.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+pub struct TagAssignmentMatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<TagAssignmentKeyMatch>,
+}
+
+/// The key fields of a `TagAssignmentMatch`, any of which may be left unset
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct TagAssignmentKeyMatch {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    workspace_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    element_type: Option<ElementType>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    device_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    interface_id: Option<String>,
+}
+
+impl TagAssignmentMatch {
+    fn key(&mut self) -> &mut TagAssignmentKeyMatch {
+        self.key.get_or_insert_with(TagAssignmentKeyMatch::default)
+    }
+}
+
+impl FilterBuilder<TagAssignmentMatch> {
+    pub fn workspace(mut self, workspace_id: &str) -> Self {
+        self.entry().key().workspace_id = Some(workspace_id.to_owned());
+        self
+    }
+    pub fn element_type(mut self, element_type: ElementType) -> Self {
+        self.entry().key().element_type = Some(element_type);
+        self
+    }
+    pub fn label(mut self, label: &str) -> Self {
+        self.entry().key().label = Some(label.to_owned());
+        self
+    }
+    pub fn value(mut self, value: &str) -> Self {
+        self.entry().key().value = Some(value.to_owned());
+        self
+    }
+    pub fn device_id(mut self, device_id: &str) -> Self {
+        self.entry().key().device_id = Some(device_id.to_owned());
+        self
+    }
+    pub fn interface_id(mut self, interface_id: &str) -> Self {
+        self.entry().key().interface_id = Some(interface_id.to_owned());
+        self
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub enum ElementType {
+    #[default]
     #[serde(rename = "ELEMENT_TYPE_UNSPECIFIED")]
     Unspecified,
     #[serde(rename = "ELEMENT_TYPE_DEVICE")]
@@ -149,7 +352,7 @@ pub enum ElementType {
     Interface,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CreatorType {
     #[serde(rename = "CREATOR_TYPE_UNSPECIFIED")]
     Unspecified,
@@ -158,3 +361,51 @@ pub enum CreatorType {
     #[serde(rename = "CREATOR_TYPE_USER")]
     User,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tag_key_builder() {
+        let key = TagKey::builder()
+            .workspace("ws1")
+            .element_type(ElementType::Device)
+            .label("site", "dc1")
+            .build();
+        assert_eq!(key.workspace_id(), Some("ws1"));
+        assert_eq!(key.element_type(), Some(ElementType::Device));
+        assert_eq!(key.label(), Some("site"));
+        assert_eq!(key.value(), Some("dc1"));
+        assert_eq!(
+            serde_json::to_string(&TagConfigRequest::new(key.clone())).unwrap(),
+            r#"{"key":{"workspaceId":"ws1","elementType":"ELEMENT_TYPE_DEVICE","label":"site","value":"dc1"}}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&TagConfig::new(key, false)).unwrap(),
+            r#"{"key":{"workspaceId":"ws1","elementType":"ELEMENT_TYPE_DEVICE","label":"site","value":"dc1"},"remove":false}"#
+        );
+    }
+
+    #[test]
+    fn test_tag_accessors() {
+        let body = r#"{"key":{"workspaceId":"","elementType":"ELEMENT_TYPE_DEVICE","label":"site","value":"dc1"},"creatorType":"CREATOR_TYPE_USER"}"#;
+        let tag: Tag = serde_json::from_str(body).unwrap();
+        assert_eq!(tag.creator_type(), Some(CreatorType::User));
+        assert_eq!(tag.key().label(), Some("site"));
+        assert_eq!(tag.key().element_type(), Some(ElementType::Device));
+    }
+
+    #[test]
+    fn test_tag_assignment_accessors() {
+        let body = r#"{"key":{"workspaceId":"","elementType":"ELEMENT_TYPE_INTERFACE","label":"role","value":"uplink","deviceId":"SSJ17200818","interfaceId":"Ethernet1"}}"#;
+        let assignment: TagAssignmentConfig = serde_json::from_str(body).unwrap();
+        assert!(!assignment.remove());
+        let key = assignment.key();
+        assert_eq!(key.element_type(), ElementType::Interface);
+        assert_eq!(key.label(), "role");
+        assert_eq!(key.value(), "uplink");
+        assert_eq!(key.device_id(), "SSJ17200818");
+        assert_eq!(key.interface_id(), "Ethernet1");
+    }
+}
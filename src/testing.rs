@@ -0,0 +1,169 @@
+use crate::middleware::Middleware;
+use crate::{Client, CloudVisionError, Config};
+use reqwest::Request;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// A canned response of a `TestServer`
+#[derive(Debug, Clone)]
+pub(crate) struct Reply {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+    delay: Duration,
+    sent: Option<usize>,
+}
+
+impl Reply {
+    pub(crate) fn new(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: body.to_owned(),
+            delay: Duration::ZERO,
+            sent: None,
+        }
+    }
+
+    pub(crate) fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_owned()));
+        self
+    }
+
+    /// Waits this long before sending the response headers
+    pub(crate) fn delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Closes the connection after the first `len` bytes of the body, so reading it fails
+    pub(crate) fn cut_after(mut self, len: usize) -> Self {
+        self.sent = Some(len);
+        self
+    }
+}
+
+/// A request received by a `TestServer`, its request line and headers followed by the body
+#[derive(Debug, Clone)]
+pub(crate) struct Received(String);
+
+impl Received {
+    /// The method and path, such as `GET /api/x`
+    pub(crate) fn line(&self) -> &str {
+        let line = self.0.lines().next().unwrap_or_default();
+        line.rsplit_once(' ').map_or(line, |(line, _)| line)
+    }
+
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.0
+            .split("\r\n\r\n")
+            .next()?
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.trim())
+    }
+}
+
+/// A plain HTTP server on localhost answering one request per connection with the next canned
+/// reply. Connections beyond the replies are closed without an answer.
+#[derive(Debug)]
+pub(crate) struct TestServer {
+    port: u16,
+    received: Arc<Mutex<Vec<Received>>>,
+}
+
+impl TestServer {
+    pub(crate) async fn start(replies: Vec<Reply>) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let log = received.clone();
+        tokio::spawn(async move {
+            let mut replies = replies.into_iter();
+            loop {
+                let (socket, _) = listener.accept().await.unwrap();
+                let reply = replies.next();
+                let log = log.clone();
+                tokio::spawn(async move { answer(socket, reply, log).await });
+            }
+        });
+        Self { port, received }
+    }
+
+    /// A token authenticated client for this server
+    pub(crate) fn client(&self) -> Client {
+        self.client_with(Config::new(String::new(), None, "token".to_string()))
+    }
+
+    /// A client for this server built from `config`, whose hostname and port are replaced
+    pub(crate) fn client_with(&self, mut config: Config) -> Client {
+        config.hostname = "127.0.0.1".to_string();
+        config.port = Some(self.port);
+        let mut client = Client::new(config).unwrap();
+        client.add_middleware(Arc::new(PlainHttp));
+        client
+    }
+
+    pub(crate) fn received(&self) -> Vec<Received> {
+        self.received.lock().unwrap().clone()
+    }
+}
+
+/// Sends requests over plain HTTP, as the client always builds `https` urls
+#[derive(Debug)]
+struct PlainHttp;
+
+impl Middleware for PlainHttp {
+    fn on_request(&self, request: &mut Request) -> Result<(), CloudVisionError> {
+        request.url_mut().set_scheme("http").unwrap();
+        Ok(())
+    }
+}
+
+async fn answer(mut socket: TcpStream, reply: Option<Reply>, log: Arc<Mutex<Vec<Received>>>) {
+    let mut request = Vec::new();
+    let mut buf = [0; 4096];
+    loop {
+        let read = socket.read(&mut buf).await.unwrap_or(0);
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..read]);
+        let text = String::from_utf8_lossy(&request);
+        if let Some((head, body)) = text.split_once("\r\n\r\n") {
+            let length = head
+                .lines()
+                .filter_map(|line| line.split_once(':'))
+                .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+                .and_then(|(_, value)| value.trim().parse().ok())
+                .unwrap_or(0);
+            if body.len() >= length {
+                break;
+            }
+        }
+    }
+    log.lock()
+        .unwrap()
+        .push(Received(String::from_utf8_lossy(&request).into_owned()));
+    let reply = match reply {
+        Some(reply) => reply,
+        None => return,
+    };
+    tokio::time::sleep(reply.delay).await;
+    let mut response = format!(
+        "HTTP/1.1 {} X\r\ncontent-length: {}\r\nconnection: close\r\n",
+        reply.status,
+        reply.body.len()
+    );
+    for (name, value) in &reply.headers {
+        response.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    response.push_str("\r\n");
+    let len = reply.sent.unwrap_or(reply.body.len());
+    response.push_str(&reply.body[..len]);
+    let _ = socket.write_all(response.as_bytes()).await;
+    let _ = socket.shutdown().await;
+}
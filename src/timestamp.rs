@@ -0,0 +1,101 @@
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+use std::fmt;
+use std::str::FromStr;
+
+/// A point in time as used by the CloudVision APIs. Values keep nanosecond precision and are
+/// written back as RFC3339 in UTC with 0, 3, 6 or 9 fractional digits, the format CloudVision
+/// sends and expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp(DateTime<Utc>);
+
+impl Timestamp {
+    /// The current time
+    pub fn now() -> Self {
+        Timestamp(Utc::now())
+    }
+    /// The underlying date and time
+    pub fn as_datetime(&self) -> &DateTime<Utc> {
+        &self.0
+    }
+    /// Time elapsed between this timestamp and now, negative if it is in the future
+    pub fn elapsed(&self) -> Duration {
+        Utc::now() - self.0
+    }
+}
+
+impl From<DateTime<Utc>> for Timestamp {
+    fn from(datetime: DateTime<Utc>) -> Self {
+        Timestamp(datetime)
+    }
+}
+
+impl From<Timestamp> for DateTime<Utc> {
+    fn from(timestamp: Timestamp) -> Self {
+        timestamp.0
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = chrono::ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(Timestamp(
+            DateTime::parse_from_rfc3339(s)?.with_timezone(&Utc),
+        ))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+    }
+}
+
+impl Serialize for Timestamp {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Timestamp {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        for time in [
+            "2022-02-10T21:43:03Z",
+            "2022-02-10T21:43:03.516Z",
+            "2022-02-10T21:43:03.516634Z",
+            "2022-02-10T21:43:03.516634412Z",
+        ] {
+            let json = format!("\"{}\"", time);
+            let timestamp: Timestamp = serde_json::from_str(&json).unwrap();
+            assert_eq!(serde_json::to_string(&timestamp).unwrap(), json);
+        }
+        // Offsets are normalized to UTC and precision is padded to a supported width
+        let timestamp: Timestamp = "2022-02-10T22:43:03.5+01:00".parse().unwrap();
+        assert_eq!(timestamp.to_string(), "2022-02-10T21:43:03.500Z");
+        assert!("yesterday".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn test_ordering() {
+        let earlier: Timestamp = "2022-02-10T21:43:03.000000001Z".parse().unwrap();
+        let later: Timestamp = "2022-02-10T21:43:03.5Z".parse().unwrap();
+        assert!(earlier < later);
+        assert_eq!(
+            *later.as_datetime() - *earlier.as_datetime(),
+            Duration::nanoseconds(499_999_999)
+        );
+    }
+}
@@ -0,0 +1,268 @@
+use crate::CloudVisionError;
+use rustls::client::{ServerCertVerified, ServerCertVerifier, WebPkiVerifier};
+use rustls::{Certificate, ClientConfig, OwnedTrustAnchor, PrivateKey, RootCertStore, ServerName};
+use rustls_pemfile::Item;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::SystemTime;
+
+/// TLS settings, read from the `[tls]` table of a config file
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default)]
+pub(crate) struct TlsConfig {
+    /// Trust the CA certificates installed in the operating system alongside the bundled public
+    /// roots, on by default so private CAs added to the OS trust store keep working
+    pub(crate) native_roots: bool,
+    /// PEM bundle of extra CA certificates trusted alongside the public roots
+    pub(crate) ca_bundle: Option<PathBuf>,
+    /// PEM certificate chain presented to the server for mTLS
+    pub(crate) client_cert: Option<PathBuf>,
+    /// PEM private key matching `client_cert`
+    pub(crate) client_key: Option<PathBuf>,
+    /// SHA-256 fingerprints of the DER encoded server certificate, in hex with optional colons
+    pub(crate) pinned_sha256: Vec<String>,
+}
+
+impl Default for TlsConfig {
+    fn default() -> Self {
+        Self {
+            native_roots: true,
+            ca_bundle: None,
+            client_cert: None,
+            client_key: None,
+            pinned_sha256: Vec::new(),
+        }
+    }
+}
+
+impl TlsConfig {
+    /// Builds the rustls configuration used by the HTTP client. When `accept_invalid_certs` is
+    /// set the certificate chain is not checked, but pinned fingerprints still are.
+    pub(crate) fn client_config(
+        &self,
+        accept_invalid_certs: bool,
+    ) -> Result<ClientConfig, CloudVisionError> {
+        let roots = self.root_store()?;
+        let verifier = PinnedVerifier {
+            webpki: (!accept_invalid_certs).then(|| WebPkiVerifier::new(roots, None)),
+            pins: self
+                .pinned_sha256
+                .iter()
+                .map(|pin| parse_fingerprint(pin))
+                .collect::<Result<_, _>>()?,
+        };
+        let builder = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_custom_certificate_verifier(Arc::new(verifier));
+        let config = match (&self.client_cert, &self.client_key) {
+            (Some(cert), Some(key)) => builder
+                .with_client_auth_cert(read_certs(cert)?, read_key(key)?)
+                .map_err(|err| tls_error(cert, &err.to_string()))?,
+            (None, None) => builder.with_no_client_auth(),
+            _ => {
+                return Err(CloudVisionError::Tls(
+                    "client_cert and client_key must be set together".to_owned(),
+                ))
+            }
+        };
+        Ok(config)
+    }
+
+    /// The bundled public roots, the operating system roots unless disabled and the CA bundle
+    fn root_store(&self) -> Result<RootCertStore, CloudVisionError> {
+        let mut roots = RootCertStore::empty();
+        roots.add_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.iter().map(|anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                anchor.subject,
+                anchor.spki,
+                anchor.name_constraints,
+            )
+        }));
+        if self.native_roots {
+            // A missing or partly unreadable OS trust store is not fatal, the public roots and
+            // the CA bundle still apply
+            match rustls_native_certs::load_native_certs() {
+                Ok(certs) => {
+                    let certs: Vec<Vec<u8>> = certs.into_iter().map(|cert| cert.0).collect();
+                    let (added, ignored) = roots.add_parsable_certificates(&certs);
+                    tracing::debug!(added, ignored, "loaded native root certificates");
+                }
+                Err(err) => tracing::warn!(error = %err, "could not load native root certificates"),
+            }
+        }
+        if let Some(path) = &self.ca_bundle {
+            for cert in read_certs(path)? {
+                roots
+                    .add(&cert)
+                    .map_err(|err| tls_error(path, &err.to_string()))?;
+            }
+        }
+        Ok(roots)
+    }
+}
+
+/// Checks the server certificate chain against the trusted roots, unless invalid certificates
+/// are accepted, and then against the pinned fingerprints if there are any
+struct PinnedVerifier {
+    webpki: Option<WebPkiVerifier>,
+    pins: Vec<[u8; 32]>,
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &Certificate,
+        intermediates: &[Certificate],
+        server_name: &ServerName,
+        scts: &mut dyn Iterator<Item = &[u8]>,
+        ocsp_response: &[u8],
+        now: SystemTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        if let Some(webpki) = &self.webpki {
+            webpki.verify_server_cert(
+                end_entity,
+                intermediates,
+                server_name,
+                scts,
+                ocsp_response,
+                now,
+            )?;
+        }
+        if !is_pinned(&self.pins, &end_entity.0) {
+            return Err(rustls::Error::General(
+                "server certificate does not match any pinned fingerprint".to_owned(),
+            ));
+        }
+        Ok(ServerCertVerified::assertion())
+    }
+}
+
+/// True if there are no pins or the certificate's SHA-256 fingerprint is one of them
+fn is_pinned(pins: &[[u8; 32]], der: &[u8]) -> bool {
+    if pins.is_empty() {
+        return true;
+    }
+    let fingerprint: [u8; 32] = Sha256::digest(der).into();
+    pins.contains(&fingerprint)
+}
+
+/// Parses a SHA-256 fingerprint such as `AB:CD:...` as printed by `openssl x509 -fingerprint`
+fn parse_fingerprint(pin: &str) -> Result<[u8; 32], CloudVisionError> {
+    let invalid = || CloudVisionError::Tls(format!("invalid SHA-256 fingerprint {}", pin));
+    let hex: Vec<u8> = pin.bytes().filter(|b| *b != b':').collect();
+    if hex.len() != 64 {
+        return Err(invalid());
+    }
+    let mut fingerprint = [0; 32];
+    for (byte, pair) in fingerprint.iter_mut().zip(hex.chunks(2)) {
+        let pair = std::str::from_utf8(pair).map_err(|_| invalid())?;
+        *byte = u8::from_str_radix(pair, 16).map_err(|_| invalid())?;
+    }
+    Ok(fingerprint)
+}
+
+fn read_pem(path: &Path) -> Result<Vec<Item>, CloudVisionError> {
+    let file = File::open(path).map_err(|err| tls_error(path, &err.to_string()))?;
+    rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|err| tls_error(path, &err.to_string()))
+}
+
+fn read_certs(path: &Path) -> Result<Vec<Certificate>, CloudVisionError> {
+    let certs: Vec<Certificate> = read_pem(path)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(der) => Some(Certificate(der)),
+            _ => None,
+        })
+        .collect();
+    if certs.is_empty() {
+        return Err(tls_error(path, "no certificates found"));
+    }
+    Ok(certs)
+}
+
+fn read_key(path: &Path) -> Result<PrivateKey, CloudVisionError> {
+    read_pem(path)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(der) | Item::RSAKey(der) | Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .ok_or_else(|| tls_error(path, "no private key found"))
+}
+
+fn tls_error(path: &Path, message: &str) -> CloudVisionError {
+    CloudVisionError::Tls(format!("{}: {}", path.display(), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fingerprint() {
+        let pin = Sha256::digest(b"certificate");
+        let hex: Vec<String> = pin.iter().map(|b| format!("{:02X}", b)).collect();
+        let expected: [u8; 32] = pin.into();
+        assert_eq!(parse_fingerprint(&hex.join(":")).unwrap(), expected);
+        assert_eq!(
+            parse_fingerprint(&hex.concat().to_lowercase()).unwrap(),
+            expected
+        );
+        assert!(is_pinned(&[expected], b"certificate"));
+        assert!(!is_pinned(&[expected], b"other"));
+        assert!(is_pinned(&[], b"other"));
+        assert!(matches!(
+            parse_fingerprint("AB:CD"),
+            Err(CloudVisionError::Tls(_))
+        ));
+        assert!(parse_fingerprint(&"zz".repeat(32)).is_err());
+    }
+
+    #[test]
+    fn test_native_roots() {
+        let tls: TlsConfig = toml::from_str("").unwrap();
+        assert!(tls.native_roots);
+        let public = TlsConfig {
+            native_roots: false,
+            ..TlsConfig::default()
+        };
+        let public = public.root_store().unwrap().len();
+        assert_eq!(public, webpki_roots::TLS_SERVER_ROOTS.len());
+        let native = rustls_native_certs::load_native_certs().unwrap_or_default();
+        let all = TlsConfig::default().root_store().unwrap().len();
+        assert!(all >= public);
+        assert!(all <= public + native.len());
+    }
+
+    #[test]
+    fn test_client_config() {
+        assert!(TlsConfig::default().client_config(false).is_ok());
+        assert!(TlsConfig::default().client_config(true).is_ok());
+
+        let path = std::env::temp_dir().join("cloudvision-empty-bundle.pem");
+        std::fs::write(&path, "not a certificate\n").unwrap();
+        let tls = TlsConfig {
+            ca_bundle: Some(path.clone()),
+            ..TlsConfig::default()
+        };
+        let err = tls.client_config(false).unwrap_err();
+        assert!(
+            matches!(err, CloudVisionError::Tls(message) if message.contains("no certificates"))
+        );
+        std::fs::remove_file(&path).unwrap();
+
+        let tls = TlsConfig {
+            client_cert: Some(path),
+            ..TlsConfig::default()
+        };
+        assert!(matches!(
+            tls.client_config(false),
+            Err(CloudVisionError::Tls(_))
+        ));
+    }
+}